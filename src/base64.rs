@@ -0,0 +1,93 @@
+//! The standard base64 alphabet, as used by `bodyBase64` in project configs
+//! and the request journal.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as base64 text, with padding.
+pub fn encode(bytes: &[u8]) -> String {
+  let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+  for chunk in bytes.chunks(3) {
+    let buffer = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |buffer, (i, &b)| buffer | (b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(ALPHABET[(buffer >> (18 - 6 * i) & 63) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+
+  encoded
+}
+
+/// Decodes base64 text. Padding is optional and whitespace is ignored, so that
+/// long values can be wrapped.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+  let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+  let mut buffer = 0u32;
+  let mut bits = 0;
+  let mut padding = 0;
+
+  for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+    if c == b'=' {
+      padding += 1;
+      continue;
+    }
+    // Data after padding is invalid.
+    if padding > 0 {
+      return None;
+    }
+
+    let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+    buffer = (buffer << 6) | value;
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      decoded.push((buffer >> bits) as u8);
+      buffer &= (1 << bits) - 1;
+    }
+  }
+
+  // A single leftover character can not encode a whole byte.
+  if bits >= 6 || padding > 2 {
+    return None;
+  }
+
+  Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encodes_with_padding() {
+    assert_eq!(encode(b"hello"), "aGVsbG8=");
+    assert_eq!(encode(b"hello!"), "aGVsbG8h");
+    assert_eq!(encode(b"hi"), "aGk=");
+    assert_eq!(encode(b""), "");
+    assert_eq!(
+      decode(&encode(&[0, 255, 128, 7])).unwrap(),
+      [0, 255, 128, 7]
+    );
+  }
+
+  #[test]
+  fn decodes_with_and_without_padding() {
+    assert_eq!(decode("aGVsbG8=").unwrap(), b"hello");
+    assert_eq!(decode("aGVsbG8").unwrap(), b"hello");
+    assert_eq!(decode("aGVs\nbG8h").unwrap(), b"hello!");
+    assert_eq!(decode("").unwrap(), b"");
+  }
+
+  #[test]
+  fn rejects_invalid_input() {
+    assert!(decode("a").is_none());
+    assert!(decode("aGV*").is_none());
+    assert!(decode("aG==VsbG8").is_none());
+  }
+}
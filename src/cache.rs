@@ -0,0 +1,343 @@
+use crate::{
+  config::{Problem, ProjectConfig},
+  helpers,
+};
+use std::{
+  collections::HashMap,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock},
+  time::SystemTime,
+};
+
+/// A shared cache of parsed project configs.
+///
+/// The config files may also be edited directly on disk, so every lookup
+/// compares the file's modification time and size with the ones it was parsed
+/// from, and reloads the file when they differ.
+pub struct ProjectCache {
+  /// The directory of the projects' config files.
+  dir: PathBuf,
+  projects: RwLock<HashMap<String, Entry>>,
+}
+
+struct Entry {
+  config: Arc<ProjectConfig>,
+  stamp: Stamp,
+}
+
+/// What identifies a version of a config file.
+#[derive(PartialEq, Clone, Copy)]
+struct Stamp {
+  modified: SystemTime,
+  len: u64,
+}
+
+impl Stamp {
+  fn of(path: &Path) -> io::Result<Stamp> {
+    let metadata = fs::metadata(path)?;
+
+    Ok(Stamp {
+      modified: metadata.modified()?,
+      len: metadata.len(),
+    })
+  }
+}
+
+pub enum LoadError {
+  NotFound,
+  /// The project exists, but was meant to be created.
+  Exists,
+  Invalid(Vec<Problem>),
+  Io(io::Error),
+}
+
+/// Whether saving a project may create it, replace it or both.
+#[derive(PartialEq, Clone, Copy)]
+pub enum SaveMode {
+  Create,
+  Replace,
+  CreateOrReplace,
+}
+
+impl ProjectCache {
+  pub fn new() -> ProjectCache {
+    ProjectCache::in_dir(helpers::get_projects_dir())
+  }
+
+  /// Returns a cache of the projects of another directory than the server's.
+  pub fn in_dir(dir: PathBuf) -> ProjectCache {
+    ProjectCache {
+      dir,
+      projects: RwLock::new(HashMap::new()),
+    }
+  }
+
+  fn config_file_path(&self, name: &str) -> PathBuf {
+    self.dir.join(format!("{}.json", name))
+  }
+
+  /// Returns a project's config, loading it from disk when it is not cached or
+  /// its file has changed.
+  pub fn get(&self, name: &str) -> Result<Arc<ProjectConfig>, LoadError> {
+    let path = self.config_file_path(name);
+    let stamp = match Stamp::of(&path) {
+      Ok(stamp) => stamp,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        self.projects.write().unwrap().remove(name);
+        return Err(LoadError::NotFound);
+      }
+      Err(error) => return Err(LoadError::Io(error)),
+    };
+
+    if let Some(entry) = self.projects.read().unwrap().get(name) {
+      if entry.stamp == stamp {
+        return Ok(entry.config.clone());
+      }
+    }
+
+    let document = fs::read(&path).map_err(LoadError::Io)?;
+    let config = Arc::new(ProjectConfig::parse(&document).map_err(LoadError::Invalid)?);
+    self.projects.write().unwrap().insert(
+      name.to_string(),
+      Entry {
+        config: config.clone(),
+        stamp,
+      },
+    );
+
+    Ok(config)
+  }
+
+  /// Validates a config document, then writes it to the project's file and
+  /// caches it, unless the project's existence does not suit the mode. The
+  /// file is replaced atomically, so concurrent lookups either see the
+  /// previous version or the new one.
+  pub fn save(
+    &self,
+    name: &str,
+    document: &[u8],
+    mode: SaveMode,
+  ) -> Result<Arc<ProjectConfig>, LoadError> {
+    let config = Arc::new(ProjectConfig::parse(document).map_err(LoadError::Invalid)?);
+
+    // Hold the lock while checking for the file and replacing it, so that a
+    // concurrent save can not create the project in between, and a concurrent
+    // lookup can not cache the new file's stamp along with the previous
+    // config.
+    let mut projects = self.projects.write().unwrap();
+    let path = self.config_file_path(name);
+    match (mode, path.exists()) {
+      (SaveMode::Create, true) => return Err(LoadError::Exists),
+      (SaveMode::Replace, false) => return Err(LoadError::NotFound),
+      _ => {}
+    }
+    write(&mut projects, name, &path, document, config)
+  }
+
+  /// Replaces a project's config document with the one computed from it, which
+  /// is validated like a saved one. Concurrent updates of the project wait for
+  /// each other, so none of them is lost.
+  pub fn update<F>(&self, name: &str, update: F) -> Result<Arc<ProjectConfig>, LoadError>
+  where
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, LoadError>,
+  {
+    let mut projects = self.projects.write().unwrap();
+    let path = self.config_file_path(name);
+    let document = match fs::read(&path) {
+      Ok(document) => document,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(LoadError::NotFound),
+      Err(error) => return Err(LoadError::Io(error)),
+    };
+
+    let document = update(&document)?;
+    let config = Arc::new(ProjectConfig::parse(&document).map_err(LoadError::Invalid)?);
+    write(&mut projects, name, &path, &document, config)
+  }
+
+  /// Deletes a project's config file along with the directory of its files.
+  pub fn delete(&self, name: &str) -> Result<(), LoadError> {
+    let mut projects = self.projects.write().unwrap();
+    projects.remove(name);
+
+    match fs::remove_file(self.config_file_path(name)) {
+      Ok(()) => {}
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(LoadError::NotFound),
+      Err(error) => return Err(LoadError::Io(error)),
+    }
+    match fs::remove_dir_all(self.dir.join(name)) {
+      Err(error) if error.kind() != io::ErrorKind::NotFound => Err(LoadError::Io(error)),
+      _ => Ok(()),
+    }
+  }
+
+  /// Returns the names of the projects, sorted.
+  pub fn names(&self) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(&self.dir) {
+      Ok(entries) => entries,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(error) => return Err(error),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+      let path = entry?.path();
+      if path.extension().is_some_and(|e| e == "json") {
+        if let Some(name) = path.file_stem().and_then(|n| n.to_str()) {
+          names.push(name.to_string());
+        }
+      }
+    }
+    names.sort();
+    Ok(names)
+  }
+}
+
+/// Writes a project's config document atomically and caches its config, with
+/// the cache locked by the caller.
+fn write(
+  projects: &mut HashMap<String, Entry>,
+  name: &str,
+  path: &Path,
+  document: &[u8],
+  config: Arc<ProjectConfig>,
+) -> Result<Arc<ProjectConfig>, LoadError> {
+  let temp_path = path.with_extension("json.tmp");
+
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).map_err(LoadError::Io)?;
+  }
+
+  fs::write(&temp_path, document).map_err(LoadError::Io)?;
+  fs::rename(&temp_path, path).map_err(LoadError::Io)?;
+  let stamp = Stamp::of(path).map_err(LoadError::Io)?;
+  projects.insert(
+    name.to_string(),
+    Entry {
+      config: config.clone(),
+      stamp,
+    },
+  );
+
+  Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::{json, Value};
+  use std::{env, process, thread, time::Duration};
+
+  /// Returns a cache of an empty directory of its own.
+  fn cache(test: &str) -> ProjectCache {
+    let dir = env::temp_dir().join(format!("mockapi-{}-{}", process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    ProjectCache::in_dir(dir)
+  }
+
+  fn document(description: &str) -> Vec<u8> {
+    json!({ "description": description, "endpoints": [] })
+      .to_string()
+      .into_bytes()
+  }
+
+  fn description(cache: &ProjectCache, name: &str) -> String {
+    match cache.get(name) {
+      Ok(config) => config.description.clone(),
+      Err(_) => panic!("Can not load project `{}`.", name),
+    }
+  }
+
+  #[test]
+  fn saves_and_gets_projects() {
+    let cache = cache("save");
+
+    assert!(matches!(cache.get("shop"), Err(LoadError::NotFound)));
+    assert!(matches!(
+      cache.save("shop", &document("a"), SaveMode::Replace),
+      Err(LoadError::NotFound)
+    ));
+    assert!(cache.save("shop", &document("a"), SaveMode::Create).is_ok());
+    assert_eq!(description(&cache, "shop"), "a");
+    assert!(matches!(
+      cache.save("shop", &document("b"), SaveMode::Create),
+      Err(LoadError::Exists)
+    ));
+    assert!(matches!(
+      cache.save("shop", br#"{ "endpoints": 1 }"#, SaveMode::Replace),
+      Err(LoadError::Invalid(_))
+    ));
+    assert!(cache
+      .save("shop", &document("b"), SaveMode::Replace)
+      .is_ok());
+    assert_eq!(description(&cache, "shop"), "b");
+    assert_eq!(cache.names().unwrap(), ["shop"]);
+
+    assert!(cache.delete("shop").is_ok());
+    assert!(matches!(cache.get("shop"), Err(LoadError::NotFound)));
+    fs::remove_dir_all(&cache.dir).unwrap();
+  }
+
+  #[test]
+  fn reloads_files_edited_on_disk() {
+    let cache = cache("reload");
+    cache.save("shop", &document("a"), SaveMode::Create).ok();
+    let path = cache.config_file_path("shop");
+    assert_eq!(description(&cache, "shop"), "a");
+
+    // A different size.
+    fs::write(&path, document("bb")).unwrap();
+    assert_eq!(description(&cache, "shop"), "bb");
+
+    // The same size, but a later modification time.
+    fs::write(&path, document("cc")).unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file
+      .set_modified(SystemTime::now() + Duration::from_secs(60))
+      .unwrap();
+    assert_eq!(description(&cache, "shop"), "cc");
+    fs::remove_dir_all(&cache.dir).unwrap();
+  }
+
+  #[test]
+  fn concurrent_saves_and_updates_are_not_lost() {
+    let cache = Arc::new(cache("concurrent"));
+
+    let created = (0..8)
+      .map(|i| {
+        let cache = cache.clone();
+        thread::spawn(move || {
+          let description = i.to_string();
+          cache
+            .save("shop", &document(&description), SaveMode::Create)
+            .is_ok()
+        })
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
+      .map(|thread| thread.join().unwrap())
+      .filter(|created| *created)
+      .count();
+    assert_eq!(created, 1);
+
+    let threads: Vec<_> = (0..8)
+      .map(|i| {
+        let cache = cache.clone();
+        thread::spawn(move || {
+          let updated = cache.update("shop", |document| {
+            let mut document: Value = serde_json::from_slice(document).unwrap();
+            let endpoint = json!({ "path": format!("e{}", i), "when": [] });
+            document["endpoints"].as_array_mut().unwrap().push(endpoint);
+            Ok(serde_json::to_vec(&document).unwrap())
+          });
+          assert!(updated.is_ok());
+        })
+      })
+      .collect();
+    for thread in threads {
+      thread.join().unwrap();
+    }
+    assert_eq!(cache.get("shop").ok().unwrap().endpoints.len(), 8);
+    fs::remove_dir_all(&cache.dir).unwrap();
+  }
+}
@@ -0,0 +1,74 @@
+use crate::{
+  cache::{LoadError, ProjectCache, SaveMode},
+  config::Problem,
+  helpers, openapi,
+};
+use std::fs;
+
+const USAGE: &str = "Usage:
+  mockapi                                              Starts the server.
+  mockapi import-openapi <project> <file> [--replace]  Generates a project from an OpenAPI 3 JSON document.";
+
+/// Runs the command given on the command line, and returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+  let args: Vec<&str> = args.iter().map(String::as_str).collect();
+  let result = match args[..] {
+    ["import-openapi", name, file] => import_openapi(name, file, false),
+    ["import-openapi", name, file, "--replace"] => import_openapi(name, file, true),
+    _ => {
+      eprintln!("{}", USAGE);
+      return 2;
+    }
+  };
+
+  match result {
+    Ok(message) => {
+      println!("{}", message);
+      0
+    }
+    Err(message) => {
+      eprintln!("{}", message);
+      1
+    }
+  }
+}
+
+/// Generates a project from an OpenAPI document, which replaces an existing
+/// project only when asked to.
+fn import_openapi(name: &str, file: &str, replace: bool) -> Result<String, String> {
+  if !helpers::is_valid_project_name(name) {
+    return Err(format!("Invalid project name `{}`.", name));
+  }
+  let document = fs::read(file).map_err(|error| format!("Can not read {}: {}.", file, error))?;
+  let config = openapi::import(&document)
+    .map_err(|problems| describe("Invalid OpenAPI document.", &problems))?;
+  let document = serde_json::to_vec_pretty(&config).unwrap();
+
+  let mode = if replace {
+    SaveMode::CreateOrReplace
+  } else {
+    SaveMode::Create
+  };
+  match ProjectCache::new().save(name, &document, mode) {
+    Ok(config) => Ok(format!(
+      "Imported {} endpoints into project `{}`.",
+      config.endpoints.len(),
+      name
+    )),
+    Err(LoadError::Invalid(problems)) => Err(describe("Invalid project config.", &problems)),
+    Err(LoadError::Io(error)) => Err(error.to_string()),
+    Err(LoadError::Exists) => Err(format!(
+      "Project `{}` already exists, pass --replace to replace it.",
+      name
+    )),
+    Err(LoadError::NotFound) => Err(String::from("Project does not exist.")),
+  }
+}
+
+fn describe(error: &str, problems: &[Problem]) -> String {
+  let mut message = error.to_string();
+  for problem in problems {
+    message.push_str(&format!("\n  {}: {}", problem.pointer, problem.message));
+  }
+  message
+}
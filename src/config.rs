@@ -0,0 +1,1459 @@
+use crate::{
+  base64,
+  latency::MAX_DELAY,
+  web_server::{is_token, types::PathPattern},
+};
+use serde_json::{Map, Value};
+use std::{
+  collections::HashMap,
+  fmt,
+  path::{Component, Path},
+};
+
+/// The typed form of a project's config file, `database/projects/<name>.json`.
+#[derive(Debug, Clone)]
+pub struct ProjectConfig {
+  pub description: String,
+  pub endpoints: Vec<Endpoint>,
+  /// A delay added to the one of every condition.
+  pub latency: Delay,
+  /// Makes the random choices of the project's responses reproducible.
+  pub seed: Option<u64>,
+  /// Where the requests that match no condition are forwarded, and maybe
+  /// recorded.
+  pub proxy: Option<Proxy>,
+  /// Where the requests that match no condition are passed through, without
+  /// recording them.
+  pub fallback: Option<Target>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Proxy {
+  pub target: Target,
+  /// Whether the forwarded requests and their responses are added to the
+  /// project as conditions.
+  pub record: bool,
+}
+
+/// The base URL of an HTTP server, e.g. `http://localhost:8080/api`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Target {
+  pub host: String,
+  pub port: u16,
+  /// Prefixed to the forwarded paths, without a trailing slash.
+  pub base_path: String,
+}
+
+impl Target {
+  pub fn parse(url: &str) -> Result<Target, String> {
+    let rest = match url.split_once("://") {
+      Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+      Some(_) => return Err(String::from("Only `http` URLs are supported.")),
+      None => {
+        return Err(String::from(
+          "Must be an URL, e.g. `http://localhost:8080`.",
+        ))
+      }
+    };
+    let (authority, base_path) = match rest.find(['/', '?', '#']) {
+      Some(i) => (&rest[..i], &rest[i..]),
+      None => (rest, ""),
+    };
+    if base_path.contains(['?', '#']) {
+      return Err(String::from("Must not have a query or a fragment."));
+    }
+
+    // IPv6 addresses are enclosed in brackets, e.g. `[::1]:8080`.
+    let (host, port) = match authority.rsplit_once(':') {
+      Some((host, port)) if !port.contains(']') => {
+        let port = port.parse().map_err(|_| String::from("Invalid port."))?;
+        (host, port)
+      }
+      _ => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() || host.contains('@') {
+      return Err(String::from("Invalid host."));
+    }
+
+    Ok(Target {
+      host: host.to_string(),
+      port,
+      base_path: base_path.trim_end_matches('/').to_string(),
+    })
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+  pub path: String,
+  /// The parsed `path`, without leading and trailing slashes.
+  pub pattern: PathPattern,
+  pub when: Vec<Condition>,
+}
+
+/// An entry of an endpoint's `when` list.
+#[derive(Debug, Clone)]
+pub struct Condition {
+  pub method: String,
+  pub request: RequestRules,
+  /// The responses of the condition, of which `mode` picks one per request. A
+  /// single `response` is a list of one.
+  pub responses: Vec<MockResponse>,
+  pub mode: Mode,
+  /// The delay before responding.
+  pub delay: Delay,
+  /// When several conditions match, the one with the highest priority wins.
+  pub priority: i64,
+  /// A failure to inject instead of answering normally.
+  pub fault: Option<FaultRule>,
+  /// The scenario whose state the condition requires or changes.
+  pub scenario: Option<String>,
+  /// The state the scenario has to be in for the condition to match.
+  pub required_state: Option<String>,
+  /// The state the scenario moves to once the condition answered a request.
+  pub new_state: Option<String>,
+}
+
+/// The rules of a condition's `request` block.
+#[derive(Default, Debug, Clone)]
+pub struct RequestRules {
+  /// Rules on the parameters captured by the endpoint's path.
+  pub params: Vec<Rule>,
+  pub queries: Vec<Rule>,
+  pub headers: Vec<Rule>,
+  pub body: Option<BodyRules>,
+}
+
+impl RequestRules {
+  /// Returns the number of rules, where each field of the body's object
+  /// shorthand counts as a rule.
+  pub fn len(&self) -> usize {
+    let body = match &self.body {
+      None => 0,
+      Some(BodyRules::Partial(fields)) => fields.len(),
+      Some(BodyRules::Rules(rules)) => rules.len(),
+    };
+    self.params.len() + self.queries.len() + self.headers.len() + body
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum BodyRules {
+  /// The object shorthand, which matches the listed fields only.
+  Partial(Map<String, Value>),
+  Rules(Vec<Rule>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+  pub operator: Operator,
+  pub name: String,
+  pub value: Value,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Operator {
+  Is,
+  IsNot,
+  Contains,
+  NotContains,
+}
+
+impl Operator {
+  pub fn parse(operator: &str) -> Option<Operator> {
+    match operator {
+      "is" => Some(Operator::Is),
+      "is!" => Some(Operator::IsNot),
+      "contains" => Some(Operator::Contains),
+      "contains!" => Some(Operator::NotContains),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Operator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Operator::Is => write!(f, "is"),
+      Operator::IsNot => write!(f, "is!"),
+      Operator::Contains => write!(f, "contains"),
+      Operator::NotContains => write!(f, "contains!"),
+    }
+  }
+}
+
+/// How long to wait before responding, in milliseconds. Declared either as a
+/// number or as a distribution to draw from on every request.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Delay {
+  Fixed(u64),
+  Uniform {
+    min: f64,
+    max: f64,
+  },
+  Normal {
+    mean: f64,
+    stddev: f64,
+  },
+  /// A log-normal distribution of the given mean and standard deviation, which
+  /// has the long tail of real latencies.
+  LogNormal {
+    mean: f64,
+    stddev: f64,
+  },
+  /// Targets for percentiles, as fractions between 0 and 1 sorted along with
+  /// their delays, e.g. `[(0.5, 100.0), (0.99, 800.0)]`.
+  Percentiles(Vec<(f64, f64)>),
+}
+
+/// A condition's `fault`, injected into a share of its responses.
+#[derive(Debug, Clone)]
+pub struct FaultRule {
+  pub kind: FaultKind,
+  /// The chance of injecting the fault, between 0 and 1.
+  pub probability: f64,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum FaultKind {
+  /// Closes the connection without responding.
+  Close,
+  /// Resets the connection without responding.
+  Reset,
+  /// Closes the connection in the middle of the body.
+  Truncate,
+  /// Sends bytes that are not an HTTP response.
+  Malformed,
+  /// Sends the body in chunks of `chunk_size` bytes, every `interval`
+  /// milliseconds.
+  Drip { chunk_size: usize, interval: u64 },
+  /// Answers with a server error, or a random one of 500, 502, 503 and 504
+  /// when `status` is missing.
+  Error { status: Option<u16> },
+}
+
+/// How a condition picks one of its `responses`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Mode {
+  /// Advances on every request and sticks to the last response.
+  Sequence,
+  /// Advances on every request and starts over after the last response.
+  Cycle,
+  /// Picks a response at random, according to their weights.
+  Random,
+}
+
+impl Mode {
+  pub fn parse(mode: &str) -> Option<Mode> {
+    match mode {
+      "sequence" => Some(Mode::Sequence),
+      "cycle" => Some(Mode::Cycle),
+      "random" => Some(Mode::Random),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+  pub status: u16,
+  pub body: ResponseBody,
+  pub headers: HashMap<String, String>,
+  /// The relative chance of being picked in the `random` mode.
+  pub weight: u64,
+}
+
+impl Default for MockResponse {
+  fn default() -> Self {
+    MockResponse {
+      status: 200,
+      body: ResponseBody::Json(Value::Null),
+      headers: HashMap::new(),
+      weight: 1,
+    }
+  }
+}
+
+/// The body of a response, declared by one of `body`, `bodyBase64` and
+/// `bodyFile`.
+#[derive(Debug, Clone)]
+pub enum ResponseBody {
+  /// Sent as JSON, or empty when it is `null`.
+  Json(Value),
+  /// Decoded from `bodyBase64`.
+  Bytes(Vec<u8>),
+  /// A path relative to the project's directory, read when responding.
+  File(String),
+}
+
+/// A request pattern and how many recorded requests should match it, as
+/// posted to `/projects/:name/__verify`.
+#[derive(Debug, Clone)]
+pub struct Verification {
+  /// The method of the requests, or any method when it is missing.
+  pub method: Option<String>,
+  /// The pattern of the requests' paths, or any path when it is missing.
+  pub path: Option<PathPattern>,
+  pub request: RequestRules,
+  pub count: Count,
+}
+
+/// The bounds of a number of requests. `exactly` sets both of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Count {
+  pub at_least: Option<u64>,
+  pub at_most: Option<u64>,
+}
+
+/// A problem found while validating a config, located by a JSON pointer.
+#[derive(PartialEq, Debug)]
+pub struct Problem {
+  pub pointer: String,
+  pub message: String,
+}
+
+impl ProjectConfig {
+  /// Parses and validates a config document, collecting every problem instead
+  /// of stopping at the first one.
+  pub fn parse(document: &[u8]) -> Result<ProjectConfig, Vec<Problem>> {
+    ProjectConfig::from_value(&parse_json(document)?)
+  }
+
+  pub fn from_value(value: &Value) -> Result<ProjectConfig, Vec<Problem>> {
+    validate(value, Validator::project)
+  }
+
+  /// Returns the names of the scenarios the conditions refer to, in the order
+  /// they are declared.
+  pub fn scenarios(&self) -> Vec<&str> {
+    let mut scenarios = Vec::new();
+    for condition in self.endpoints.iter().flat_map(|e| &e.when) {
+      if let Some(scenario) = condition.scenario.as_deref() {
+        if !scenarios.contains(&scenario) {
+          scenarios.push(scenario);
+        }
+      }
+    }
+    scenarios
+  }
+}
+
+impl Verification {
+  /// Parses and validates a verification document, like a config.
+  pub fn parse(document: &[u8]) -> Result<Verification, Vec<Problem>> {
+    validate(&parse_json(document)?, Validator::verification)
+  }
+}
+
+impl Count {
+  pub fn accepts(&self, count: u64) -> bool {
+    self.at_least.is_none_or(|min| count >= min) && self.at_most.is_none_or(|max| count <= max)
+  }
+}
+
+impl fmt::Display for Count {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.at_least, self.at_most) {
+      (Some(min), Some(max)) if min == max => write!(f, "exactly {}", min),
+      (Some(min), Some(max)) => write!(f, "between {} and {}", min, max),
+      (Some(min), None) => write!(f, "at least {}", min),
+      (None, Some(max)) => write!(f, "at most {}", max),
+      (None, None) => write!(f, "any number of"),
+    }
+  }
+}
+
+/// Merges a patch into a config document, both given as JSON. The patch's
+/// `endpoints` replace the document's ones with the same path or are appended,
+/// the paths listed in `removeEndpoints` are removed, and its other fields
+/// replace the document's.
+pub fn merge(document: &[u8], patch: &[u8]) -> Result<Vec<u8>, Vec<Problem>> {
+  let mut merged = parse_json(document)?;
+  validate(&parse_json(patch)?, |validator, patch| {
+    validator.merge(&mut merged, patch)
+  })?;
+
+  Ok(serde_json::to_vec_pretty(&merged).unwrap())
+}
+
+/// Parses a JSON document, whose syntax error is a problem of the whole document.
+pub fn parse_json(document: &[u8]) -> Result<Value, Vec<Problem>> {
+  serde_json::from_slice(document).map_err(|error| {
+    vec![Problem {
+      pointer: String::new(),
+      message: format!("Invalid JSON: {}.", error),
+    }]
+  })
+}
+
+fn validate<T, F>(value: &Value, parse: F) -> Result<T, Vec<Problem>>
+where
+  F: FnOnce(&mut Validator, &Value) -> T,
+{
+  let mut validator = Validator::default();
+  let parsed = parse(&mut validator, value);
+
+  if validator.problems.is_empty() {
+    Ok(parsed)
+  } else {
+    Err(validator.problems)
+  }
+}
+
+#[derive(Default)]
+struct Validator {
+  problems: Vec<Problem>,
+}
+
+impl Validator {
+  fn problem(&mut self, pointer: &str, message: &str) {
+    self.problems.push(Problem {
+      pointer: pointer.to_string(),
+      message: message.to_string(),
+    });
+  }
+
+  fn object<'a>(&mut self, value: &'a Value, pointer: &str) -> Option<&'a Map<String, Value>> {
+    let object = value.as_object();
+    if object.is_none() {
+      self.problem(pointer, "Must be an object.");
+    }
+    object
+  }
+
+  fn array<'a>(&mut self, value: &'a Value, pointer: &str) -> &'a [Value] {
+    match value {
+      Value::Array(items) => items,
+      Value::Null => {
+        self.problem(pointer, "Is required.");
+        &[]
+      }
+      _ => {
+        self.problem(pointer, "Must be an array.");
+        &[]
+      }
+    }
+  }
+
+  fn string(&mut self, value: &Value, pointer: &str) -> String {
+    match value {
+      Value::String(string) => string.clone(),
+      Value::Null => {
+        self.problem(pointer, "Is required.");
+        String::new()
+      }
+      _ => {
+        self.problem(pointer, "Must be a string.");
+        String::new()
+      }
+    }
+  }
+
+  fn non_negative_number(&mut self, value: &Value, pointer: &str) -> f64 {
+    match value.as_f64() {
+      Some(number) if number >= 0.0 => number,
+      _ => {
+        self.problem(pointer, "Must be a non-negative number.");
+        0.0
+      }
+    }
+  }
+
+  fn project(&mut self, value: &Value) -> ProjectConfig {
+    let mut config = ProjectConfig {
+      description: String::new(),
+      endpoints: Vec::new(),
+      latency: Delay::Fixed(0),
+      seed: None,
+      proxy: None,
+      fallback: None,
+    };
+    if self.object(value, "").is_none() {
+      return config;
+    }
+
+    if !value["description"].is_null() {
+      config.description = self.string(&value["description"], "/description");
+    }
+
+    if !value["latency"].is_null() {
+      config.latency = self.delay(&value["latency"], "/latency");
+    }
+
+    match &value["seed"] {
+      Value::Null => {}
+      seed => match seed.as_u64() {
+        Some(seed) => config.seed = Some(seed),
+        None => self.problem("/seed", "Must be a non-negative integer."),
+      },
+    }
+
+    if !value["proxy"].is_null() && !value["fallback"].is_null() {
+      self.problem("", "Must declare only one of `proxy` and `fallback`.");
+    } else if !value["proxy"].is_null() {
+      config.proxy = self.proxy(&value["proxy"], "/proxy");
+    } else if !value["fallback"].is_null() {
+      let fallback = self.string(&value["fallback"], "/fallback");
+      match Target::parse(&fallback) {
+        Ok(target) => config.fallback = Some(target),
+        Err(message) => self.problem("/fallback", &message),
+      }
+    }
+
+    for (i, endpoint) in self
+      .array(&value["endpoints"], "/endpoints")
+      .iter()
+      .enumerate()
+    {
+      config
+        .endpoints
+        .push(self.endpoint(endpoint, &format!("/endpoints/{}", i)));
+    }
+
+    config
+  }
+
+  fn merge(&mut self, document: &mut Value, patch: &Value) {
+    let Some(fields) = self.object(patch, "") else {
+      return;
+    };
+    if !document["endpoints"].is_array() {
+      document["endpoints"] = Value::Array(Vec::new());
+    }
+    let same_path = |endpoint: &Value, path: &str| {
+      endpoint["path"]
+        .as_str()
+        .is_some_and(|p| p.trim_matches('/') == path.trim_matches('/'))
+    };
+
+    for (key, value) in fields {
+      match key.as_str() {
+        "endpoints" => {
+          for (i, endpoint) in self.array(value, "/endpoints").iter().enumerate() {
+            let pointer = format!("/endpoints/{}", i);
+            if self.object(endpoint, &pointer).is_none() {
+              continue;
+            }
+            let path = self.string(&endpoint["path"], &format!("{}/path", pointer));
+            let endpoints = document["endpoints"].as_array_mut().unwrap();
+            match endpoints.iter_mut().find(|e| same_path(e, &path)) {
+              Some(existing) => *existing = endpoint.clone(),
+              None => endpoints.push(endpoint.clone()),
+            }
+          }
+        }
+        "removeEndpoints" => {
+          for (i, path) in self.array(value, "/removeEndpoints").iter().enumerate() {
+            let pointer = format!("/removeEndpoints/{}", i);
+            let path = self.string(path, &pointer);
+            let endpoints = document["endpoints"].as_array_mut().unwrap();
+            let len = endpoints.len();
+            endpoints.retain(|e| !same_path(e, &path));
+            if endpoints.len() == len {
+              self.problem(&pointer, "Is not the path of an endpoint.");
+            }
+          }
+        }
+        _ => document[key] = value.clone(),
+      }
+    }
+  }
+
+  /// Parses a proxy, either as an object or as the shorthand of its target.
+  fn proxy(&mut self, value: &Value, pointer: &str) -> Option<Proxy> {
+    let (target, record) = match value {
+      Value::String(_) => (value, &Value::Null),
+      _ => {
+        self.object(value, pointer)?;
+        (&value["target"], &value["record"])
+      }
+    };
+
+    let target_pointer = match value {
+      Value::String(_) => pointer.to_string(),
+      _ => format!("{}/target", pointer),
+    };
+    let target = match Target::parse(&self.string(target, &target_pointer)) {
+      Ok(target) => target,
+      Err(message) => {
+        if !target.is_null() {
+          self.problem(&target_pointer, &message);
+        }
+        return None;
+      }
+    };
+
+    let record = match record {
+      Value::Null => false,
+      Value::Bool(record) => *record,
+      _ => {
+        self.problem(&format!("{}/record", pointer), "Must be a boolean.");
+        false
+      }
+    };
+
+    Some(Proxy { target, record })
+  }
+
+  fn endpoint(&mut self, value: &Value, pointer: &str) -> Endpoint {
+    let mut endpoint = Endpoint {
+      path: String::new(),
+      pattern: PathPattern {
+        segments: Vec::new(),
+      },
+      when: Vec::new(),
+    };
+    if self.object(value, pointer).is_none() {
+      return endpoint;
+    }
+
+    let path_pointer = format!("{}/path", pointer);
+    endpoint.path = self.string(&value["path"], &path_pointer);
+    match PathPattern::parse(endpoint.path.trim_matches('/')) {
+      Ok(pattern) => endpoint.pattern = pattern,
+      Err(message) => self.problem(&path_pointer, &message),
+    }
+
+    let when_pointer = format!("{}/when", pointer);
+    for (i, condition) in self.array(&value["when"], &when_pointer).iter().enumerate() {
+      endpoint
+        .when
+        .push(self.condition(condition, &format!("{}/{}", when_pointer, i)));
+    }
+
+    endpoint
+  }
+
+  fn condition(&mut self, value: &Value, pointer: &str) -> Condition {
+    let mut condition = Condition {
+      method: String::new(),
+      request: RequestRules::default(),
+      responses: vec![MockResponse::default()],
+      mode: Mode::Sequence,
+      delay: Delay::Fixed(0),
+      priority: 0,
+      fault: None,
+      scenario: None,
+      required_state: None,
+      new_state: None,
+    };
+    if self.object(value, pointer).is_none() {
+      return condition;
+    }
+
+    let method_pointer = format!("{}/method", pointer);
+    condition.method = self
+      .string(&value["method"], &method_pointer)
+      .to_uppercase();
+    if !value["method"].is_null() && !is_token(&condition.method) {
+      self.problem(&method_pointer, "Must be an HTTP method.");
+    }
+
+    if !value["request"].is_null() {
+      condition.request = self.request(&value["request"], &format!("{}/request", pointer));
+    }
+
+    if !value["response"].is_null() && !value["responses"].is_null() {
+      self.problem(
+        pointer,
+        "Must declare only one of `response` and `responses`.",
+      );
+    } else if !value["response"].is_null() {
+      condition.responses =
+        vec![self.response(&value["response"], &format!("{}/response", pointer))];
+    } else if !value["responses"].is_null() {
+      let responses_pointer = format!("{}/responses", pointer);
+      let responses = self.array(&value["responses"], &responses_pointer);
+      if responses.is_empty() && value["responses"].is_array() {
+        self.problem(&responses_pointer, "Must not be empty.");
+      }
+      if !responses.is_empty() {
+        condition.responses = Vec::new();
+      }
+      for (i, response) in responses.iter().enumerate() {
+        let response = self.response(response, &format!("{}/{}", responses_pointer, i));
+        condition.responses.push(response);
+      }
+      let total = condition
+        .responses
+        .iter()
+        .try_fold(0, |total: u64, r| total.checked_add(r.weight));
+      if total.is_none() {
+        let message = format!("The weights must add up to at most {}.", u64::MAX);
+        self.problem(&responses_pointer, &message);
+      }
+    }
+
+    if !value["mode"].is_null() {
+      let mode_pointer = format!("{}/mode", pointer);
+      let mode = self.string(&value["mode"], &mode_pointer);
+      match Mode::parse(&mode) {
+        Some(mode) => condition.mode = mode,
+        None => self.problem(
+          &mode_pointer,
+          "Must be one of `sequence`, `cycle` and `random`.",
+        ),
+      }
+    }
+
+    match &value["priority"] {
+      Value::Null => {}
+      priority => match priority.as_i64() {
+        Some(priority) => condition.priority = priority,
+        None => self.problem(&format!("{}/priority", pointer), "Must be an integer."),
+      },
+    }
+
+    if !value["delay"].is_null() {
+      condition.delay = self.delay(&value["delay"], &format!("{}/delay", pointer));
+    }
+
+    if !value["fault"].is_null() {
+      condition.fault = self.fault(&value["fault"], &format!("{}/fault", pointer));
+    }
+
+    for (key, state) in [
+      ("scenario", &mut condition.scenario),
+      ("requiredState", &mut condition.required_state),
+      ("newState", &mut condition.new_state),
+    ] {
+      if !value[key].is_null() {
+        *state = Some(self.string(&value[key], &format!("{}/{}", pointer, key)));
+      }
+    }
+    if condition.scenario.is_none() {
+      for key in ["requiredState", "newState"] {
+        if !value[key].is_null() {
+          let message = "Requires a `scenario`.";
+          self.problem(&format!("{}/{}", pointer, key), message);
+        }
+      }
+    }
+
+    condition
+  }
+
+  /// Parses a delay, either as a number of milliseconds or as a distribution
+  /// object with a `type`.
+  fn delay(&mut self, value: &Value, pointer: &str) -> Delay {
+    if value.is_number() {
+      return match value.as_u64() {
+        Some(delay) if delay <= MAX_DELAY => Delay::Fixed(delay),
+        Some(_) => {
+          self.problem(
+            pointer,
+            &format!("Must be at most {} milliseconds.", MAX_DELAY),
+          );
+          Delay::Fixed(0)
+        }
+        None => {
+          self.problem(pointer, "Must be a non-negative integer or an object.");
+          Delay::Fixed(0)
+        }
+      };
+    }
+    let Some(object) = self.object(value, pointer) else {
+      return Delay::Fixed(0);
+    };
+
+    let number = |validator: &mut Validator, key: &str| {
+      let pointer = format!("{}/{}", pointer, escape_pointer(key));
+      let number = validator.non_negative_number(&value[key], &pointer);
+      if number > MAX_DELAY as f64 {
+        validator.problem(
+          &pointer,
+          &format!("Must be at most {} milliseconds.", MAX_DELAY),
+        );
+        return MAX_DELAY as f64;
+      }
+      number
+    };
+    let type_pointer = format!("{}/type", pointer);
+    let delay = match value["type"].as_str() {
+      Some("uniform") => Delay::Uniform {
+        min: number(self, "min"),
+        max: number(self, "max"),
+      },
+      Some("normal") => Delay::Normal {
+        mean: number(self, "mean"),
+        stddev: number(self, "stddev"),
+      },
+      Some("lognormal") => Delay::LogNormal {
+        mean: number(self, "mean"),
+        stddev: number(self, "stddev"),
+      },
+      Some("percentiles") => {
+        let mut targets = Vec::new();
+        for key in object.keys().filter(|key| *key != "type") {
+          let percentile = key
+            .strip_prefix('p')
+            .and_then(|p| p.parse::<f64>().ok())
+            .filter(|p| *p > 0.0 && *p <= 100.0);
+          match percentile {
+            Some(percentile) => targets.push((percentile / 100.0, number(self, key))),
+            None => {
+              let message = "Must be a percentile above `p0` and at most `p100`, e.g. `p99`.";
+              self.problem(&format!("{}/{}", pointer, escape_pointer(key)), message);
+            }
+          }
+        }
+        targets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Delay::Percentiles(targets)
+      }
+      _ => {
+        self.problem(
+          &type_pointer,
+          "Must be one of `uniform`, `normal`, `lognormal` and `percentiles`.",
+        );
+        return Delay::Fixed(0);
+      }
+    };
+
+    match &delay {
+      Delay::Uniform { min, max } if min > max => {
+        self.problem(pointer, "`min` can not be greater than `max`.");
+      }
+      Delay::LogNormal { mean, .. } if *mean == 0.0 => {
+        self.problem(&format!("{}/mean", pointer), "Must be positive.");
+      }
+      Delay::Percentiles(targets) if targets.is_empty() => {
+        self.problem(pointer, "Must have at least one percentile, e.g. `p99`.");
+      }
+      Delay::Percentiles(targets) if targets.windows(2).any(|w| w[0].1 > w[1].1) => {
+        self.problem(pointer, "The delays must grow with the percentiles.");
+      }
+      _ => {}
+    }
+
+    delay
+  }
+
+  /// Parses a fault, either as an object or as the shorthand of its type, e.g.
+  /// `"reset"`.
+  fn fault(&mut self, value: &Value, pointer: &str) -> Option<FaultRule> {
+    let object = match value {
+      Value::String(_) => Map::from_iter([("type".to_string(), value.clone())]),
+      _ => self.object(value, pointer)?.clone(),
+    };
+    let field = |key: &str| object.get(key).unwrap_or(&Value::Null);
+    let type_pointer = format!("{}/type", pointer);
+    let kind = match self.string(field("type"), &type_pointer).as_str() {
+      "close" => FaultKind::Close,
+      "reset" => FaultKind::Reset,
+      "truncate" => FaultKind::Truncate,
+      "malformed" => FaultKind::Malformed,
+      "drip" => {
+        let mut positive = |key: &str, default: u64| match field(key) {
+          Value::Null => default,
+          value => value.as_u64().filter(|v| *v > 0).unwrap_or_else(|| {
+            self.problem(
+              &format!("{}/{}", pointer, key),
+              "Must be a positive integer.",
+            );
+            default
+          }),
+        };
+        FaultKind::Drip {
+          chunk_size: positive("chunkSize", 16) as usize,
+          interval: positive("interval", 100),
+        }
+      }
+      "error" => match field("status") {
+        Value::Null => FaultKind::Error { status: None },
+        status => match status.as_u64().filter(|s| (500..=599).contains(s)) {
+          Some(status) => FaultKind::Error {
+            status: Some(status as u16),
+          },
+          None => {
+            let message = "Must be an integer between 500 and 599.";
+            self.problem(&format!("{}/status", pointer), message);
+            return None;
+          }
+        },
+      },
+      "" => return None,
+      _ => {
+        self.problem(
+          &type_pointer,
+          "Must be one of `close`, `reset`, `truncate`, `malformed`, `drip` and `error`.",
+        );
+        return None;
+      }
+    };
+
+    let probability = match field("probability") {
+      Value::Null => 1.0,
+      probability => match probability.as_f64().filter(|p| (0.0..=1.0).contains(p)) {
+        Some(probability) => probability,
+        None => {
+          let message = "Must be a number between 0 and 1.";
+          self.problem(&format!("{}/probability", pointer), message);
+          return None;
+        }
+      },
+    };
+
+    Some(FaultRule { kind, probability })
+  }
+
+  fn verification(&mut self, value: &Value) -> Verification {
+    let mut verification = Verification {
+      method: None,
+      path: None,
+      request: RequestRules::default(),
+      // Without a count, the requests are expected to have been received.
+      count: Count {
+        at_least: Some(1),
+        at_most: None,
+      },
+    };
+    if self.object(value, "").is_none() {
+      return verification;
+    }
+
+    if !value["method"].is_null() {
+      let method = self.string(&value["method"], "/method").to_uppercase();
+      if !is_token(&method) {
+        self.problem("/method", "Must be an HTTP method.");
+      }
+      verification.method = Some(method);
+    }
+
+    if !value["path"].is_null() {
+      let path = self.string(&value["path"], "/path");
+      match PathPattern::parse(path.trim_matches('/')) {
+        Ok(pattern) => verification.path = Some(pattern),
+        Err(message) => self.problem("/path", &message),
+      }
+    }
+
+    if !value["request"].is_null() {
+      verification.request = self.request(&value["request"], "/request");
+    }
+
+    if !value["count"].is_null() {
+      verification.count = self.count(&value["count"], "/count");
+    }
+
+    verification
+  }
+
+  fn count(&mut self, value: &Value, pointer: &str) -> Count {
+    let mut count = Count::default();
+    let Some(fields) = self.object(value, pointer) else {
+      return count;
+    };
+
+    for (key, field) in fields {
+      let bound = match field.as_u64() {
+        Some(bound) => Some(bound),
+        None => {
+          let message = "Must be a non-negative integer.";
+          self.problem(&format!("{}/{}", pointer, escape_pointer(key)), message);
+          continue;
+        }
+      };
+      match key.as_str() {
+        "exactly" => (count.at_least, count.at_most) = (bound, bound),
+        "atLeast" => count.at_least = bound,
+        "atMost" => count.at_most = bound,
+        _ => self.problem(
+          &format!("{}/{}", pointer, escape_pointer(key)),
+          "Must be one of `exactly`, `atLeast` and `atMost`.",
+        ),
+      }
+    }
+
+    if fields.contains_key("exactly") && fields.len() > 1 {
+      self.problem(pointer, "`exactly` can not be combined with other bounds.");
+    } else if fields.is_empty() {
+      self.problem(pointer, "Must have `exactly`, `atLeast` or `atMost`.");
+    } else if let (Some(min), Some(max)) = (count.at_least, count.at_most) {
+      if min > max {
+        self.problem(pointer, "`atLeast` can not be greater than `atMost`.");
+      }
+    }
+
+    count
+  }
+
+  fn request(&mut self, value: &Value, pointer: &str) -> RequestRules {
+    let mut rules = RequestRules::default();
+    if self.object(value, pointer).is_none() {
+      return rules;
+    }
+
+    rules.params = self.rules(&value["params"], &format!("{}/params", pointer));
+    rules.queries = self.rules(&value["queries"], &format!("{}/queries", pointer));
+    rules.headers = self.rules(&value["headers"], &format!("{}/headers", pointer));
+    rules.body = match &value["body"] {
+      Value::Null => None,
+      Value::Object(fields) => Some(BodyRules::Partial(fields.clone())),
+      body => Some(BodyRules::Rules(
+        self.rules(body, &format!("{}/body", pointer)),
+      )),
+    };
+
+    rules
+  }
+
+  /// Parses either an array of rules or an object of `is` shorthands.
+  fn rules(&mut self, value: &Value, pointer: &str) -> Vec<Rule> {
+    match value {
+      Value::Null => Vec::new(),
+      Value::Object(shorthands) => shorthands
+        .iter()
+        .map(|(name, value)| Rule {
+          operator: Operator::Is,
+          name: name.clone(),
+          value: value.clone(),
+        })
+        .collect(),
+      Value::Array(rules) => rules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rule)| self.rule(rule, &format!("{}/{}", pointer, i)))
+        .collect(),
+      _ => {
+        self.problem(pointer, "Must be an array of rules or an object.");
+        Vec::new()
+      }
+    }
+  }
+
+  fn rule(&mut self, value: &Value, pointer: &str) -> Option<Rule> {
+    self.object(value, pointer)?;
+
+    let operator_pointer = format!("{}/operator", pointer);
+    let operator = match &value["operator"] {
+      Value::Null => Some(Operator::Is),
+      Value::String(operator) => Operator::parse(operator),
+      _ => None,
+    };
+    if operator.is_none() {
+      self.problem(
+        &operator_pointer,
+        "Must be one of `is`, `is!`, `contains` and `contains!`.",
+      );
+    }
+
+    let name = self.string(&value["name"], &format!("{}/name", pointer));
+
+    if value.get("value").is_none() {
+      self.problem(&format!("{}/value", pointer), "Is required.");
+    }
+
+    Some(Rule {
+      operator: operator?,
+      name,
+      value: value["value"].clone(),
+    })
+  }
+
+  fn response(&mut self, value: &Value, pointer: &str) -> MockResponse {
+    let mut response = MockResponse::default();
+    if self.object(value, pointer).is_none() {
+      return response;
+    }
+
+    match &value["status"] {
+      Value::Null => {}
+      status => match status.as_u64().filter(|s| (100..=599).contains(s)) {
+        Some(status) => response.status = status as u16,
+        None => self.problem(
+          &format!("{}/status", pointer),
+          "Must be an integer between 100 and 599.",
+        ),
+      },
+    }
+
+    response.body = self.response_body(value, pointer);
+
+    match &value["weight"] {
+      Value::Null => {}
+      weight => match weight.as_u64().filter(|w| *w > 0) {
+        Some(weight) => response.weight = weight,
+        None => self.problem(
+          &format!("{}/weight", pointer),
+          "Must be a positive integer.",
+        ),
+      },
+    }
+
+    let headers_pointer = format!("{}/headers", pointer);
+    if !value["headers"].is_null() {
+      if let Some(headers) = self.object(&value["headers"], &headers_pointer) {
+        for (name, value) in headers {
+          match value {
+            Value::String(value) => {
+              response.headers.insert(name.clone(), value.clone());
+            }
+            Value::Number(_) | Value::Bool(_) => {
+              response.headers.insert(name.clone(), value.to_string());
+            }
+            _ => self.problem(
+              &format!("{}/{}", headers_pointer, escape_pointer(name)),
+              "Must be a string.",
+            ),
+          }
+        }
+      }
+    }
+
+    response
+  }
+
+  fn response_body(&mut self, value: &Value, pointer: &str) -> ResponseBody {
+    let declared: Vec<&str> = ["body", "bodyBase64", "bodyFile"]
+      .into_iter()
+      .filter(|key| value.get(*key).is_some())
+      .collect();
+    if declared.len() > 1 {
+      self.problem(
+        pointer,
+        "Must declare only one of `body`, `bodyBase64` and `bodyFile`.",
+      );
+    }
+
+    match declared.first() {
+      Some(&"bodyBase64") => {
+        let body_pointer = format!("{}/bodyBase64", pointer);
+        let text = self.string(&value["bodyBase64"], &body_pointer);
+        match base64::decode(&text) {
+          Some(bytes) => ResponseBody::Bytes(bytes),
+          None => {
+            self.problem(&body_pointer, "Must be valid base64.");
+            ResponseBody::Bytes(Vec::new())
+          }
+        }
+      }
+      Some(&"bodyFile") => {
+        let body_pointer = format!("{}/bodyFile", pointer);
+        let path = self.string(&value["bodyFile"], &body_pointer);
+        // Only paths inside the project's directory can be served.
+        let is_inside = Path::new(&path)
+          .components()
+          .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if path.is_empty() || !is_inside {
+          self.problem(
+            &body_pointer,
+            "Must be a relative path inside the project's directory.",
+          );
+        }
+        ResponseBody::File(path)
+      }
+      _ => ResponseBody::Json(value["body"].clone()),
+    }
+  }
+}
+
+/// Escapes a key so that it can be used as a JSON pointer segment.
+pub fn escape_pointer(key: &str) -> String {
+  key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn applies_defaults() {
+    let config = ProjectConfig::parse(
+      br#"{ "endpoints": [{ "path": "hello", "when": [{ "method": "get" }] }] }"#,
+    )
+    .unwrap();
+    let condition = &config.endpoints[0].when[0];
+
+    assert_eq!(condition.method, "GET");
+    assert_eq!(condition.delay, Delay::Fixed(0));
+    assert_eq!(condition.mode, Mode::Sequence);
+    assert_eq!(condition.responses.len(), 1);
+    assert_eq!(condition.responses[0].status, 200);
+    assert!(condition.responses[0].headers.is_empty());
+  }
+
+  #[test]
+  fn collects_every_problem() {
+    let problems = ProjectConfig::parse(
+      br#"{
+        "endpoints": [{
+          "when": [{
+            "method": "GET",
+            "request": { "queries": [{ "operator": "equals", "name": "id", "value": 1 }] },
+            "response": { "status": 1000, "headers": { "a/b": {} } },
+            "delay": -1
+          }, {
+            "method": "GET",
+            "mode": "random",
+            "responses": [{ "weight": 18446744073709551615 }, { "weight": 1 }]
+          }]
+        }]
+      }"#,
+    )
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+
+    assert_eq!(
+      pointers,
+      vec![
+        "/endpoints/0/path",
+        "/endpoints/0/when/0/request/queries/0/operator",
+        "/endpoints/0/when/0/response/status",
+        "/endpoints/0/when/0/response/headers/a~1b",
+        "/endpoints/0/when/0/delay",
+        "/endpoints/0/when/1/responses",
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_json() {
+    let problems = ProjectConfig::parse(b"{").unwrap_err();
+
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].pointer, "");
+  }
+
+  #[test]
+  fn parses_scenarios() {
+    let config = ProjectConfig::from_value(&serde_json::json!({ "endpoints": [
+      { "path": "order", "when": [
+        { "method": "GET", "scenario": "pay", "requiredState": "Started" },
+        { "method": "GET", "scenario": "pay", "requiredState": "paid" },
+      ]},
+      { "path": "order/pay", "when": [
+        { "method": "POST", "scenario": "pay", "newState": "paid" },
+        { "method": "DELETE", "scenario": "cancel" },
+      ]},
+    ]}))
+    .unwrap();
+
+    assert_eq!(config.scenarios(), vec!["pay", "cancel"]);
+    assert_eq!(
+      config.endpoints[1].when[0].new_state.as_deref(),
+      Some("paid")
+    );
+
+    let problems = ProjectConfig::parse(
+      br#"{ "endpoints": [{ "path": "a", "when": [{ "method": "GET", "newState": "b" }] }] }"#,
+    )
+    .unwrap_err();
+    assert_eq!(problems[0].pointer, "/endpoints/0/when/0/newState");
+  }
+
+  #[test]
+  fn parses_delays() {
+    let config = ProjectConfig::from_value(&serde_json::json!({
+      "latency": { "type": "uniform", "min": 10, "max": 20 },
+      "seed": 7,
+      "endpoints": [{ "path": "a", "when": [
+        { "method": "GET", "delay": 5 },
+        { "method": "GET", "delay": { "type": "percentiles", "p99": 900, "p50": 100 } },
+      ]}],
+    }))
+    .unwrap();
+
+    assert_eq!(
+      config.latency,
+      Delay::Uniform {
+        min: 10.0,
+        max: 20.0
+      }
+    );
+    assert_eq!(config.seed, Some(7));
+    assert_eq!(config.endpoints[0].when[0].delay, Delay::Fixed(5));
+    assert_eq!(
+      config.endpoints[0].when[1].delay,
+      Delay::Percentiles(vec![(0.5, 100.0), (0.99, 900.0)])
+    );
+
+    let problems = ProjectConfig::parse(
+      br#"{ "latency": { "type": "normal", "mean": -1 }, "endpoints": [{ "path": "a", "when": [
+        { "method": "GET", "delay": { "type": "uniform", "min": 2, "max": 1 } },
+        { "method": "GET", "delay": { "type": "percentiles", "p50": 100, "p90": 50, "x": 1 } },
+        { "method": "GET", "delay": 3600001 },
+        { "method": "GET", "delay": { "type": "uniform", "min": 1, "max": 1e300 } }
+      ] }] }"#,
+    )
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(
+      pointers,
+      vec![
+        "/latency/mean",
+        "/latency/stddev",
+        "/endpoints/0/when/0/delay",
+        "/endpoints/0/when/1/delay/x",
+        "/endpoints/0/when/1/delay",
+        "/endpoints/0/when/2/delay",
+        "/endpoints/0/when/3/delay/max",
+      ]
+    );
+  }
+
+  #[test]
+  fn parses_faults() {
+    let config =
+      ProjectConfig::from_value(&serde_json::json!({ "endpoints": [{ "path": "a", "when": [
+        { "method": "GET", "fault": "reset" },
+        { "method": "GET", "fault": { "type": "drip", "chunkSize": 4 } },
+        { "method": "GET", "fault": { "type": "error", "probability": 0.25 } },
+      ]}]}))
+      .unwrap();
+    let faults: Vec<&FaultRule> = config.endpoints[0]
+      .when
+      .iter()
+      .map(|c| c.fault.as_ref().unwrap())
+      .collect();
+
+    assert_eq!(faults[0].kind, FaultKind::Reset);
+    assert_eq!(faults[0].probability, 1.0);
+    assert_eq!(
+      faults[1].kind,
+      FaultKind::Drip {
+        chunk_size: 4,
+        interval: 100
+      }
+    );
+    assert_eq!(faults[2].kind, FaultKind::Error { status: None });
+    assert_eq!(faults[2].probability, 0.25);
+
+    let problems = ProjectConfig::parse(
+      br#"{ "endpoints": [{ "path": "a", "when": [
+        { "method": "GET", "fault": "explode" },
+        { "method": "GET", "fault": { "type": "error", "status": 404 } },
+        { "method": "GET", "fault": { "type": "close", "probability": 2 } }
+      ] }] }"#,
+    )
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(
+      pointers,
+      vec![
+        "/endpoints/0/when/0/fault/type",
+        "/endpoints/0/when/1/fault/status",
+        "/endpoints/0/when/2/fault/probability",
+      ]
+    );
+  }
+
+  #[test]
+  fn merges_endpoints_by_path() {
+    let document = br#"{ "description": "a", "endpoints": [
+      { "path": "a", "when": [] },
+      { "path": "b", "when": [] },
+      { "path": "c", "when": [] }
+    ] }"#;
+    let patch = br#"{ "description": "b", "removeEndpoints": ["/c"], "endpoints": [
+      { "path": "/b", "when": [{ "method": "GET" }] },
+      { "path": "d", "when": [] }
+    ] }"#;
+
+    let merged: Value = serde_json::from_slice(&merge(document, patch).unwrap()).unwrap();
+    assert_eq!(merged["description"], "b");
+    let paths: Vec<&str> = merged["endpoints"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|e| e["path"].as_str().unwrap())
+      .collect();
+    assert_eq!(paths, vec!["a", "/b", "d"]);
+    assert_eq!(merged["endpoints"][1]["when"][0]["method"], "GET");
+
+    let problems = merge(
+      document,
+      br#"{ "removeEndpoints": ["x"], "endpoints": [{}] }"#,
+    )
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(pointers, vec!["/endpoints/0/path", "/removeEndpoints/0"]);
+  }
+
+  #[test]
+  fn parses_proxy_targets() {
+    let target = |url: &str| Target::parse(url);
+
+    assert_eq!(
+      target("http://localhost:8080/api/").unwrap(),
+      Target {
+        host: String::from("localhost"),
+        port: 8080,
+        base_path: String::from("/api"),
+      }
+    );
+    assert_eq!(target("HTTP://example.com").unwrap().port, 80);
+    assert_eq!(target("http://[::1]:81").unwrap().host, "::1");
+    assert!(target("https://example.com").is_err());
+    assert!(target("example.com").is_err());
+    assert!(target("http://example.com:x").is_err());
+    assert!(target("http://example.com/?a=1").is_err());
+
+    let config = ProjectConfig::from_value(&serde_json::json!({
+      "proxy": { "target": "http://localhost:9000", "record": true },
+      "endpoints": [],
+    }))
+    .unwrap();
+    let proxy = config.proxy.unwrap();
+    assert_eq!(proxy.target.port, 9000);
+    assert!(proxy.record);
+
+    let config = ProjectConfig::from_value(&serde_json::json!({
+      "fallback": "http://dev.example.com/v1",
+      "endpoints": [],
+    }))
+    .unwrap();
+    assert_eq!(config.fallback.unwrap().base_path, "/v1");
+
+    let problems =
+      ProjectConfig::parse(br#"{ "proxy": "http://a", "fallback": "http://b", "endpoints": [] }"#)
+        .unwrap_err();
+    assert_eq!(problems[0].pointer, "");
+  }
+
+  #[test]
+  fn parses_verifications() {
+    let verification = Verification::parse(
+      br#"{ "method": "post", "path": "/payments/:id", "count": { "exactly": 1 } }"#,
+    )
+    .unwrap();
+
+    assert_eq!(verification.method.as_deref(), Some("POST"));
+    assert!(verification.path.unwrap().matches("payments/7").is_some());
+    assert!(verification.count.accepts(1));
+    assert!(!verification.count.accepts(2));
+    assert_eq!(verification.count.to_string(), "exactly 1");
+
+    let verification = Verification::parse(b"{}").unwrap();
+    assert!(!verification.count.accepts(0));
+    assert!(verification.count.accepts(3));
+
+    let problems =
+      Verification::parse(br#"{ "count": { "exactly": 1, "atMost": 2, "about": 3 } }"#)
+        .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(pointers, vec!["/count/about", "/count"]);
+  }
+
+  #[test]
+  fn validates_response_bodies() {
+    let problems = ProjectConfig::parse(
+      br#"{ "endpoints": [{ "path": "a", "when": [
+        { "method": "GET", "response": { "bodyBase64": "a" } },
+        { "method": "GET", "response": { "bodyFile": "../secret" } },
+        { "method": "GET", "response": { "body": {}, "bodyFile": "a.png" } }
+      ] }] }"#,
+    )
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+
+    assert_eq!(
+      pointers,
+      vec![
+        "/endpoints/0/when/0/response/bodyBase64",
+        "/endpoints/0/when/1/response/bodyFile",
+        "/endpoints/0/when/2/response",
+      ]
+    );
+  }
+}
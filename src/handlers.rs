@@ -1,90 +1,806 @@
-use crate::{helpers, web_server};
-use serde_json::Value;
-use std::{collections::HashMap, fs, fs::read_to_string};
-use web_server::types::{Nested, Request, Response};
+use crate::{
+  cache::{LoadError, ProjectCache, SaveMode},
+  config::{self, FaultKind, ProjectConfig, ResponseBody, Target, Verification},
+  har, helpers,
+  journal::{self, Journal, Matched},
+  latency, matcher, openapi, proxy,
+  random::Rng,
+  scenarios::{self, Scenarios, States},
+  sequences::{self, Sequences},
+  template, web_server,
+};
+use serde_json::{json, Value};
+use std::{
+  fs,
+  io::{self, Write},
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+use web_server::types::{Fault, Nested, PathPattern, Request, Response};
 
-/// Returns a closure that saves a project's config.
-pub fn save_config() -> impl Fn(Request) -> Response {
-  |request: Request| {
-    let file_path = helpers::config_file_path_from_request(&request);
+/// Returns a closure that saves a project's config. The sequences of its
+/// conditions start over, since they may have changed.
+pub fn save_config(
+  cache: Arc<ProjectCache>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
 
-    if request.method == "POST" && file_path.exists() {
-      let mut body = Nested::new();
-      body.insert_string("error".to_string(), "Project already exists.".to_string());
+    if let Err(error) = cache.save(name, &request.body, save_mode(&request)) {
+      return save_error_response(error);
+    }
+    sequences.reset(name);
+
+    let mut body = Nested::new();
+    body.insert_string("result".to_string(), "ok".to_string());
+    Response::json(200, body, None)
+  }
+}
 
-      return Response::json(400, body, None);
-    } else if request.method == "PUT" && !file_path.exists() {
+/// Returns a closure that generates a project's config from an OpenAPI 3
+/// document. Like a config, it is `POST`ed to create a project and `PUT` to
+/// replace one.
+pub fn import_openapi(
+  cache: Arc<ProjectCache>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    let document = match openapi::import(&request.body) {
+      Ok(config) => serde_json::to_vec_pretty(&config).unwrap(),
+      Err(problems) => {
+        return helpers::problems_response(422, "Invalid OpenAPI document.", &problems)
+      }
+    };
+
+    let config = match cache.save(name, &document, save_mode(&request)) {
+      Ok(config) => config,
+      Err(error) => return save_error_response(error),
+    };
+    sequences.reset(name);
+
+    helpers::json_response(
+      200,
+      &json!({ "result": "ok", "endpoints": config.endpoints.len() }),
+    )
+  }
+}
+
+/// Returns a closure that generates a project's config from a HAR document,
+/// whose `stripHeaders` query lists the response headers to drop, e.g.
+/// `Date,Set-Cookie`.
+pub fn import_har(
+  cache: Arc<ProjectCache>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    let strip: Vec<&str> = match request.queries.get("stripHeaders") {
+      Some(names) => names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect(),
+      None => Vec::new(),
+    };
+    let document = match har::import(&request.body, &strip) {
+      Ok(config) => serde_json::to_vec_pretty(&config).unwrap(),
+      Err(problems) => return helpers::problems_response(422, "Invalid HAR document.", &problems),
+    };
+
+    let config = match cache.save(name, &document, save_mode(&request)) {
+      Ok(config) => config,
+      Err(error) => return save_error_response(error),
+    };
+    sequences.reset(name);
+
+    helpers::json_response(
+      200,
+      &json!({ "result": "ok", "endpoints": config.endpoints.len() }),
+    )
+  }
+}
+
+/// Returns a closure that renders a project's config as an OpenAPI 3
+/// document.
+pub fn export_openapi(cache: Arc<ProjectCache>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    match cache.get(name) {
+      Ok(config) => helpers::json_response(200, &openapi::export(name, &config)),
+      Err(error) => load_error_response(error),
+    }
+  }
+}
+
+/// Converts a failure to save a project's config into a response, where
+/// replacing a project that does not exist is a bad request.
+fn save_error_response(error: LoadError) -> Response {
+  match error {
+    LoadError::NotFound => {
       let mut body = Nested::new();
       body.insert_string("error".to_string(), "Project does not exist.".to_string());
+      Response::json(400, body, None)
+    }
+    error => load_error_response(error),
+  }
+}
 
-      return Response::json(400, body, None);
+/// Returns how a request may save a project: `POST` creates it and `PUT`
+/// replaces it.
+fn save_mode(request: &Request) -> SaveMode {
+  if request.method == "PUT" {
+    SaveMode::Replace
+  } else {
+    SaveMode::Create
+  }
+}
+
+/// Returns a closure that merges a patch into a project's config, replacing
+/// or appending endpoints by path.
+pub fn patch_config(
+  cache: Arc<ProjectCache>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    let updated = cache.update(name, |document| {
+      config::merge(document, &request.body).map_err(LoadError::Invalid)
+    });
+    if let Err(error) = updated {
+      return load_error_response(error);
     }
+    sequences.reset(name);
 
-    fs::write(file_path, request.body).unwrap();
+    helpers::json_response(200, &json!({ "result": "ok" }))
+  }
+}
 
-    let mut body = Nested::new();
-    body.insert_string("result".to_string(), "ok".to_string());
-    Response::json(200, body, None)
+/// Returns a closure that deletes a project, along with its files and the
+/// states of its scenarios and sequences.
+pub fn delete_config(
+  cache: Arc<ProjectCache>,
+  scenarios: Arc<Scenarios>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    if let Err(error) = cache.delete(name) {
+      return load_error_response(error);
+    }
+    scenarios.reset(name);
+    sequences.reset(name);
+
+    helpers::json_response(200, &json!({ "result": "ok" }))
   }
 }
 
-/// Returns a closure that mocks a request of a given project.
-pub fn mock_request() -> impl Fn(Request) -> Response {
-  |request: Request| {
-    let config = helpers::get_project_config_file_path(request.matches.get(0).unwrap());
-    if !config.exists() {
+/// Returns a closure that lists the projects with their description and
+/// number of endpoints.
+pub fn list_projects(cache: Arc<ProjectCache>) -> impl Fn(Request) -> Response {
+  move |_: Request| {
+    let names = match cache.names() {
+      Ok(names) => names,
+      Err(error) => return load_error_response(LoadError::Io(error)),
+    };
+
+    let projects: Vec<Value> = names
+      .into_iter()
+      .map(|name| match cache.get(&name) {
+        Ok(config) => json!({
+          "name": name,
+          "description": config.description,
+          "endpoints": config.endpoints.len(),
+        }),
+        Err(LoadError::Invalid(_)) => json!({ "name": name, "error": "Invalid project config." }),
+        Err(LoadError::NotFound | LoadError::Exists) => {
+          json!({ "name": name, "error": "Project does not exist." })
+        }
+        Err(LoadError::Io(error)) => json!({ "name": name, "error": error.to_string() }),
+      })
+      .collect();
+
+    helpers::json_response(200, &json!({ "projects": projects }))
+  }
+}
+
+/// Returns a closure that mocks a request of a given project and records it in
+/// the journal.
+pub fn mock_request(
+  cache: Arc<ProjectCache>,
+  journal: Arc<Journal>,
+  scenarios: Arc<Scenarios>,
+  sequences: Arc<Sequences>,
+) -> impl Fn(Request) -> Response {
+  move |mut request: Request| {
+    let received_at = SystemTime::now();
+    let started = Instant::now();
+    let project = request.matches.first().unwrap().clone();
+    let actual_path = request
+      .matches
+      .get(1)
+      .unwrap()
+      .trim_matches('/')
+      .to_string();
+
+    // Only the requests to existing projects are journaled, so that the
+    // journal does not fill up with the ones to any name.
+    let answer = mock(
+      &cache,
+      &scenarios,
+      &sequences,
+      &project,
+      &actual_path,
+      &mut request,
+    );
+    let (response, matched) = match answer {
+      Ok(answer) => answer,
+      Err(response) => return response,
+    };
+
+    journal.record(journal::Entry {
+      id: 0,
+      project,
+      method: request.method,
+      path: actual_path,
+      queries: request.queries,
+      headers: request.headers,
+      body: request.body,
+      matched,
+      status: response.status,
+      received_at,
+      duration: started.elapsed(),
+    });
+
+    response
+  }
+}
+
+/// Answers a mock request, along with the condition that matched it, or
+/// answers with an error when the project can not be loaded.
+fn mock(
+  cache: &ProjectCache,
+  scenarios: &Scenarios,
+  sequences: &Sequences,
+  project: &str,
+  path: &str,
+  request: &mut Request,
+) -> Result<(Response, Option<Matched>), Response> {
+  if !helpers::is_valid_project_name(project) {
+    let body = json!({ "error": "Invalid project name." });
+    return Err(helpers::json_response(400, &body));
+  }
+  let config = match cache.get(project) {
+    Ok(config) => config,
+    Err(LoadError::NotFound) => {
       let mut body = Nested::new();
       body.insert_string("error".to_string(), "Project does not exist.".to_string());
-      return Response::json(400, body, None);
+      return Err(Response::json(400, body, None));
+    }
+    Err(error) => return Err(load_error_response(error)),
+  };
+
+  // The condition is chosen from a snapshot of the scenarios' states, without
+  // holding their lock. Its scenario only moves if no other request moved one
+  // meanwhile, otherwise the condition is chosen again.
+  let has_scenarios = !config.scenarios().is_empty();
+  let selection = loop {
+    let states = if has_scenarios {
+      scenarios.states(project)
+    } else {
+      States::new()
+    };
+    let selection = match matcher::select(&config, path, request, &states) {
+      Some(selection) => selection,
+      None => break None,
+    };
+    if !has_scenarios {
+      break Some(selection);
     }
 
-    let config = read_to_string(config).unwrap();
-    let value: Value = serde_json::from_str(&config).unwrap();
-    let endpoints = &value["endpoints"];
-    let actual_path = request.matches.get(1).unwrap();
-    let actual_method = request.method.to_uppercase();
+    let condition = &config.endpoints[selection.endpoint].when[selection.condition];
+    let moved = match (&condition.scenario, &condition.new_state) {
+      (Some(scenario), Some(state)) => Some((scenario.as_str(), state.as_str())),
+      _ => None,
+    };
+    if scenarios.transition(project, &states, moved) {
+      break Some(selection);
+    }
+  };
 
-    // TODO: Implement these matching.
-    // let actual_queries = &request.queries;
-    // let actual_headers = &request.headers;
-    // let actual_body = &request.body;
+  if let Some(selection) = selection {
+    // The captured parameters can be rendered like the rest of the request.
+    request.params = selection.params;
+    let endpoint = &config.endpoints[selection.endpoint];
+    let matched = Matched {
+      endpoint: endpoint.path.clone(),
+      condition: selection.condition,
+    };
+    let call = sequences.next(project, selection.endpoint, selection.condition);
+    let response = respond(
+      project,
+      &config,
+      (selection.endpoint, selection.condition),
+      call,
+      request,
+    );
+    return Ok((response, Some(matched)));
+  }
 
-    for endpoint in endpoints.as_array().unwrap() {
-      let expected_path = &endpoint["path"].as_str().unwrap();
-      if expected_path != actual_path {
-        continue;
-      }
+  // Only the proxy records the requests it forwards, whereas the fallback
+  // passes them through.
+  let forwarded = match (&config.proxy, &config.fallback) {
+    (Some(proxy), _) => Some((&proxy.target, proxy.record)),
+    (None, Some(target)) => Some((target, false)),
+    (None, None) => None,
+  };
+  if let Some((target, record)) = forwarded {
+    let response = proxied_response(cache, project, target, record, path, request);
+    return Ok((response, None));
+  }
 
-      let when = &endpoint["when"];
-      for condition in when.as_array().unwrap() {
-        let expected_method = &condition["method"];
-        if actual_method != expected_method.as_str().unwrap().to_uppercase() {
-          continue;
-        }
-        let expected_delay = &condition["delay"].as_u64().unwrap();
-        let expected_response = &condition["response"];
-        let expected_headers = &expected_response["headers"];
-        let mut expected_headers_map = HashMap::new();
-        for (key, value) in expected_headers.as_object().unwrap() {
-          expected_headers_map.insert(key.to_string(), value.to_string());
-        }
+  let states = scenarios.states(project);
+  Ok((
+    unmatched_response(project, &config, path, request, &states),
+    None,
+  ))
+}
+
+/// Answers a request that matched no condition with the response of another
+/// server, and records them both when asked to.
+fn proxied_response(
+  cache: &ProjectCache,
+  project: &str,
+  target: &Target,
+  record: bool,
+  path: &str,
+  request: &Request,
+) -> Response {
+  let response = match proxy::forward(target, path, request) {
+    Ok(response) => response,
+    Err(error) => {
+      let error = format!(
+        "Can not forward the request to {}:{}: {}",
+        target.host, target.port, error
+      );
+      return helpers::json_response(502, &json!({ "error": error }));
+    }
+  };
 
-        if expected_delay > &0 {
-          std::thread::sleep(std::time::Duration::from_millis(*expected_delay));
+  if record {
+    let report = match proxy::record(cache, project, path, request, &response) {
+      Ok(()) => vec![format!(
+        "Recorded {} {} in project `{}`.",
+        request.method, path, project
+      )],
+      Err(LoadError::Invalid(problems)) => {
+        let mut report = vec![format!("Can not record {} {}:", request.method, path)];
+        for problem in problems {
+          report.push(format!("  {}: {}", problem.pointer, problem.message));
         }
+        report
+      }
+      Err(LoadError::NotFound | LoadError::Exists) => vec![format!(
+        "Can not record, project `{}` does not exist.",
+        project
+      )],
+      Err(LoadError::Io(error)) => vec![format!(
+        "Can not record {} {}: {}",
+        request.method, path, error
+      )],
+    };
+    log(&report);
+  }
+
+  response
+}
+
+/// Prints the lines of a report to the server log at once, so that the reports
+/// of concurrent requests do not interleave.
+fn log(report: &[String]) {
+  let mut stdout = io::stdout().lock();
+  for line in report {
+    let _ = writeln!(stdout, "{}", line);
+  }
+}
+
+/// How many near misses are listed when no condition matches a request.
+const NEAR_MISS_LIMIT: usize = 3;
 
-        return Response {
-          status: expected_response["status"].as_u64().unwrap() as u16,
-          body: expected_response["body"].to_string(),
-          headers: expected_headers_map,
-        };
+/// Answers a request that matched no condition with the closest conditions and
+/// the rules they failed, which are logged as well.
+fn unmatched_response(
+  project: &str,
+  config: &ProjectConfig,
+  path: &str,
+  request: &mut Request,
+  states: &States,
+) -> Response {
+  let near_misses = matcher::diagnose(config, path, request, states, NEAR_MISS_LIMIT);
+
+  let mut report = vec![format!(
+    "No condition of project `{}` matched {} {}.",
+    project, request.method, path
+  )];
+  let near_misses: Vec<Value> = near_misses
+    .iter()
+    .map(|near_miss| {
+      let endpoint = &config.endpoints[near_miss.endpoint];
+      let condition = &endpoint.when[near_miss.condition];
+      let failures: Vec<Value> = near_miss
+        .mismatches
+        .iter()
+        .map(|m| {
+          let rule = match (&m.name, m.operator) {
+            (Some(name), Some(operator)) => format!("{} `{}` {}", m.rule, name, operator),
+            _ => m.rule.to_string(),
+          };
+          report.push(format!(
+            "  {} when[{}] {}: expected {}, got {}",
+            endpoint.path, near_miss.condition, rule, m.expected, m.actual
+          ));
+          json!({
+            "rule": m.rule,
+            "operator": m.operator.map(|o| o.to_string()),
+            "name": m.name,
+            "expected": m.expected,
+            "actual": m.actual,
+          })
+        })
+        .collect();
+
+      json!({
+        "endpoint": endpoint.path,
+        "condition": near_miss.condition,
+        "method": condition.method,
+        "failures": failures,
+      })
+    })
+    .collect();
+  log(&report);
+
+  helpers::json_response(
+    400,
+    &json!({
+      "error": "No condition matched the request.",
+      "request": { "method": request.method, "path": path },
+      "nearMisses": near_misses,
+    }),
+  )
+}
+
+/// Answers a request with a response of the condition it matched, given by its
+/// endpoint's and its own index, which answered `call` requests before.
+fn respond(
+  project: &str,
+  config: &ProjectConfig,
+  (endpoint, index): (usize, usize),
+  call: u64,
+  request: &Request,
+) -> Response {
+  let condition = &config.endpoints[endpoint].when[index];
+  // A seeded project draws the same numbers for the same call of a condition
+  // on every run, whatever the order of concurrent requests.
+  let mut rng = match config.seed {
+    Some(seed) => Rng::derived(seed, &[endpoint as u64, index as u64, call]),
+    None => Rng::from_entropy(),
+  };
+
+  let delay =
+    latency::sample(&config.latency, &mut rng) + latency::sample(&condition.delay, &mut rng);
+  if !delay.is_zero() {
+    std::thread::sleep(delay);
+  }
+  let fault = condition
+    .fault
+    .as_ref()
+    .filter(|fault| rng.next_f64() < fault.probability)
+    .map(|fault| &fault.kind);
+  if let Some(FaultKind::Error { status }) = fault {
+    const STATUSES: [u16; 4] = [500, 502, 503, 504];
+    let status = status.unwrap_or_else(|| STATUSES[rng.range(0, 3) as usize]);
+    return helpers::json_response(status, &json!({ "error": "Injected fault." }));
+  }
+  let response = sequences::pick(condition, call, &mut rng);
+
+  // Both the JSON body and the headers may refer to the request.
+  let mut context = template::Context::new(request, &mut rng);
+  let body = match &response.body {
+    ResponseBody::Json(Value::Null) => Vec::new(),
+    ResponseBody::Json(body) => context.render_json(body).to_string().into_bytes(),
+    ResponseBody::Bytes(body) => body.clone(),
+    ResponseBody::File(path) => match fs::read(helpers::get_project_dir(project).join(path)) {
+      Ok(body) => body,
+      Err(error) => {
+        let error = format!("Can not read body file `{}`: {}", path, error);
+        return helpers::json_response(500, &json!({ "error": error }));
       }
+    },
+  };
+
+  let headers = response
+    .headers
+    .iter()
+    .map(|(name, value)| (name.clone(), context.render_header(value)))
+    .collect();
+
+  Response {
+    status: response.status,
+    body,
+    headers,
+    fault: fault.and_then(server_fault),
+  }
+}
+
+/// Converts a fault of a condition into the one the server injects while
+/// writing the response.
+fn server_fault(fault: &FaultKind) -> Option<Fault> {
+  match fault {
+    FaultKind::Close => Some(Fault::Close),
+    FaultKind::Reset => Some(Fault::Reset),
+    FaultKind::Truncate => Some(Fault::Truncate),
+    FaultKind::Malformed => Some(Fault::Malformed),
+    FaultKind::Drip {
+      chunk_size,
+      interval,
+    } => Some(Fault::Drip {
+      chunk_size: *chunk_size,
+      interval: Duration::from_millis(*interval),
+    }),
+    FaultKind::Error { .. } => None,
+  }
+}
+
+/// Returns a closure that lists the journal's entries, filtered by the
+/// `project`, `path` and `method` queries.
+pub fn list_requests(journal: Arc<Journal>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let filter = match journal_filter(&request) {
+      Ok(filter) => filter,
+      Err(response) => return response,
+    };
+    let entries: Vec<Value> = journal
+      .list(&filter)
+      .iter()
+      .map(journal::Entry::to_json)
+      .collect();
+
+    helpers::json_response(200, &json!({ "requests": entries }))
+  }
+}
+
+/// Returns a closure that answers a single entry of the journal.
+pub fn get_request(journal: Arc<Journal>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let entry = request
+      .params
+      .get("id")
+      .and_then(|id| id.parse().ok())
+      .and_then(|id| journal.get(id));
+
+    match entry {
+      Some(entry) => helpers::json_response(200, &entry.to_json()),
+      None => helpers::json_response(404, &json!({ "error": "Request does not exist." })),
     }
+  }
+}
+
+/// Returns a closure that clears the journal's entries, filtered like the
+/// listed ones.
+pub fn clear_requests(journal: Arc<Journal>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let filter = match journal_filter(&request) {
+      Ok(filter) => filter,
+      Err(response) => return response,
+    };
+    let cleared = journal.clear(&filter);
 
-    Response {
-      status: 400,
-      body: "Not implemented.".to_string(),
-      headers: HashMap::new(),
+    helpers::json_response(200, &json!({ "cleared": cleared }))
+  }
+}
+
+fn journal_filter(request: &Request) -> Result<journal::Filter, Response> {
+  let path = match request.queries.get("path") {
+    Some(path) => match PathPattern::parse(path.trim_matches('/')) {
+      Ok(path) => Some(path),
+      Err(error) => {
+        let error = format!("Invalid path filter: {}", error);
+        return Err(helpers::json_response(400, &json!({ "error": error })));
+      }
+    },
+    None => None,
+  };
+
+  Ok(journal::Filter {
+    project: request.queries.get("project").cloned(),
+    path,
+    method: request.queries.get("method").cloned(),
+  })
+}
+
+/// Returns a closure that counts the journal's requests of a project that
+/// match a verification, and tells whether the count is the expected one.
+pub fn verify_requests(journal: Arc<Journal>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let project = match helpers::project_name(&request) {
+      Ok(project) => project,
+      Err(response) => return response,
+    };
+    let verification = match Verification::parse(&request.body) {
+      Ok(verification) => verification,
+      Err(problems) => {
+        return helpers::problems_response(422, "Invalid verification.", &problems);
+      }
+    };
+
+    // Forgotten requests might have matched, so the count would be wrong.
+    let evicted = journal.evicted(project);
+    if evicted > 0 {
+      let error = format!(
+        "The journal forgot {} requests of the project, which might have matched. Clear them with `DELETE /__admin/requests?project={}` before the requests to verify.",
+        evicted, project
+      );
+      return helpers::json_response(409, &json!({ "error": error }));
+    }
+
+    let filter = journal::Filter {
+      project: Some(project.to_string()),
+      ..journal::Filter::default()
+    };
+    let matching: Vec<Value> = journal
+      .list(&filter)
+      .iter()
+      .filter(|entry| matcher::verifies(&verification, entry))
+      .map(journal::Entry::to_json)
+      .collect();
+    let count = matching.len() as u64;
+    let passed = verification.count.accepts(count);
+
+    let mut body = json!({
+      "result": if passed { "pass" } else { "fail" },
+      "count": count,
+      "requests": matching,
+    });
+    if !passed {
+      body["error"] = json!(format!(
+        "Expected {} matching requests, received {}.",
+        verification.count, count
+      ));
+    }
+    helpers::json_response(200, &body)
+  }
+}
+
+/// Returns a closure that answers the current states of a project's
+/// scenarios.
+pub fn list_scenarios(
+  cache: Arc<ProjectCache>,
+  scenarios: Arc<Scenarios>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let project = match helpers::project_name(&request) {
+      Ok(project) => project,
+      Err(response) => return response,
+    };
+    let config = match cache.get(project) {
+      Ok(config) => config,
+      Err(error) => return load_error_response(error),
+    };
+
+    let states = scenarios.states(project);
+    let listed: serde_json::Map<String, Value> = config
+      .scenarios()
+      .into_iter()
+      .map(|scenario| {
+        (
+          scenario.to_string(),
+          json!(scenarios::state_of(&states, scenario)),
+        )
+      })
+      .collect();
+
+    helpers::json_response(200, &json!({ "scenarios": listed }))
+  }
+}
+
+/// Returns a closure that moves a scenario of a project to the state in the
+/// request's body, e.g. `{ "state": "paid" }`.
+pub fn set_scenario(
+  cache: Arc<ProjectCache>,
+  scenarios: Arc<Scenarios>,
+) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let project = match helpers::project_name(&request) {
+      Ok(project) => project,
+      Err(response) => return response,
+    };
+    let scenario = request.params.get("scenario").unwrap();
+    let config = match cache.get(project) {
+      Ok(config) => config,
+      Err(error) => return load_error_response(error),
+    };
+    if !config.scenarios().contains(&scenario.as_str()) {
+      return helpers::json_response(404, &json!({ "error": "Scenario does not exist." }));
+    }
+
+    let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+    match body["state"].as_str() {
+      Some(state) => {
+        scenarios.set(project, scenario, state);
+        helpers::json_response(200, &json!({ "result": "ok" }))
+      }
+      None => helpers::json_response(400, &json!({ "error": "A `state` string is required." })),
+    }
+  }
+}
+
+/// Returns a closure that moves every scenario of a project back to the
+/// `Started` state.
+pub fn reset_scenarios(scenarios: Arc<Scenarios>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let project = match helpers::project_name(&request) {
+      Ok(project) => project,
+      Err(response) => return response,
+    };
+    scenarios.reset(project);
+
+    helpers::json_response(200, &json!({ "result": "ok" }))
+  }
+}
+
+/// Returns a closure that starts the response sequences of a project's
+/// conditions over.
+pub fn reset_sequences(sequences: Arc<Sequences>) -> impl Fn(Request) -> Response {
+  move |request: Request| {
+    let project = match helpers::project_name(&request) {
+      Ok(project) => project,
+      Err(response) => return response,
+    };
+    sequences.reset(project);
+
+    helpers::json_response(200, &json!({ "result": "ok" }))
+  }
+}
+
+/// Converts a failure to load or save a project's config into a response.
+fn load_error_response(error: LoadError) -> Response {
+  match error {
+    LoadError::NotFound => {
+      let mut body = Nested::new();
+      body.insert_string("error".to_string(), "Project does not exist.".to_string());
+      Response::json(404, body, None)
+    }
+    LoadError::Exists => {
+      let mut body = Nested::new();
+      body.insert_string("error".to_string(), "Project already exists.".to_string());
+      Response::json(400, body, None)
+    }
+    LoadError::Invalid(problems) => {
+      helpers::problems_response(422, "Invalid project config.", &problems)
+    }
+    LoadError::Io(error) => {
+      let mut body = Nested::new();
+      body.insert_string("error".to_string(), error.to_string());
+      Response::json(500, body, None)
     }
   }
 }
@@ -0,0 +1,334 @@
+use crate::{
+  base64,
+  config::{self, Problem},
+  latency::MAX_DELAY,
+  proxy,
+  web_server::{
+    client, decode_uri_component, insert_header,
+    types::{PathPattern, Request, Response},
+  },
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The requests of a path that are answered by the same condition, with their
+/// responses in the recorded order.
+struct Recorded {
+  condition: Value,
+  responses: Vec<Value>,
+  /// The time the server took to respond to each request, in milliseconds.
+  delays: Vec<f64>,
+}
+
+/// Generates a project's config from a HAR document, with an endpoint per
+/// recorded path. Each method and distinct queries and JSON body make up a
+/// condition, which replays the recorded responses in order after the observed
+/// delay. The response headers listed in `strip` are dropped. The problems
+/// point into the HAR document.
+pub fn import(document: &[u8], strip: &[&str]) -> Result<Value, Vec<Problem>> {
+  let har = config::parse_json(document)?;
+  let entries = har["log"]["entries"].as_array().ok_or_else(|| {
+    vec![Problem {
+      pointer: String::from("/log/entries"),
+      message: String::from("Must be an array."),
+    }]
+  })?;
+
+  let mut problems = Vec::new();
+  let mut endpoints: Vec<(String, Vec<Recorded>)> = Vec::new();
+  for (i, entry) in entries.iter().enumerate() {
+    let pointer = format!("/log/entries/{}", i);
+    let url = match entry["request"]["url"].as_str() {
+      Some(url) => url,
+      None => {
+        problems.push(Problem {
+          pointer: format!("{}/request/url", pointer),
+          message: String::from("Must be a string."),
+        });
+        continue;
+      }
+    };
+    // Requests that got no response, such as the blocked ones, have the status
+    // 0, and the ones of other schemes, such as `data:` URLs, reached no
+    // server.
+    let status = entry["response"]["status"]
+      .as_u64()
+      .filter(|status| (100..=599).contains(status));
+    let status = match status {
+      Some(status) if url.starts_with("http://") || url.starts_with("https://") => status as u16,
+      _ => continue,
+    };
+
+    let (path, query) = split_url(url);
+    // The recorded path becomes the endpoint's, which would match far more
+    // requests than the recorded one if it read as a pattern.
+    if !path.split('/').all(PathPattern::is_literal) {
+      problems.push(Problem {
+        pointer: format!("{}/request/url", pointer),
+        message: String::from(
+          "Must not have path segments that read as a pattern, such as `:id`, `*` or `**`.",
+        ),
+      });
+      continue;
+    }
+    let request = recorded_request(&entry["request"], &path, query);
+    let response = match recorded_response(&entry["response"], status, strip) {
+      Some(response) => response,
+      None => {
+        problems.push(Problem {
+          pointer: format!("{}/response/content/text", pointer),
+          message: String::from("Must be valid base64."),
+        });
+        continue;
+      }
+    };
+    let mut condition = proxy::recorded_condition(&request, &response, strip);
+    let recorded_response = condition["response"].take();
+    let delay = entry["timings"]["wait"]
+      .as_f64()
+      .filter(|wait| *wait >= 0.0)
+      .or_else(|| entry["time"].as_f64())
+      .unwrap_or(0.0);
+
+    let recordings = match endpoints.iter_mut().find(|(p, _)| *p == path) {
+      Some((_, recordings)) => recordings,
+      None => {
+        endpoints.push((path, Vec::new()));
+        &mut endpoints.last_mut().unwrap().1
+      }
+    };
+    match recordings.iter_mut().find(|r| r.condition == condition) {
+      Some(recorded) => {
+        recorded.responses.push(recorded_response);
+        recorded.delays.push(delay);
+      }
+      None => recordings.push(Recorded {
+        condition,
+        responses: vec![recorded_response],
+        delays: vec![delay],
+      }),
+    }
+  }
+
+  if !problems.is_empty() {
+    return Err(problems);
+  }
+  let endpoints: Vec<Value> = endpoints
+    .into_iter()
+    .map(|(path, recordings)| {
+      let when: Vec<Value> = recordings.into_iter().map(condition).collect();
+      json!({ "path": path, "when": when })
+    })
+    .collect();
+  Ok(json!({ "endpoints": endpoints }))
+}
+
+/// Returns the condition of recorded requests, which answers with their
+/// responses in sequence, after their average delay, up to the longest delay a
+/// config may have.
+fn condition(recorded: Recorded) -> Value {
+  let mut condition = recorded.condition;
+  let mut responses = recorded.responses;
+  if responses.len() == 1 {
+    condition["response"] = responses.remove(0);
+  } else {
+    condition.as_object_mut().unwrap().remove("response");
+    condition["responses"] = json!(responses);
+  }
+
+  let delay = recorded.delays.iter().sum::<f64>() / recorded.delays.len() as f64;
+  if delay.round() >= 1.0 {
+    condition["delay"] = json!((delay.round() as u64).min(MAX_DELAY));
+  }
+  condition
+}
+
+/// Splits a URL into the path, without leading and trailing slashes, and the
+/// query string.
+fn split_url(url: &str) -> (String, &str) {
+  let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+  let target = rest.find('/').map_or("", |i| &rest[i..]);
+  let target = target.split('#').next().unwrap_or("");
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+  (path.trim_matches('/').to_string(), query)
+}
+
+/// Returns a recorded request, whose queries are decoded from the URL like the
+/// server decodes the ones it receives.
+fn recorded_request(request: &Value, path: &str, query: &str) -> Request {
+  let queries = query
+    .split('&')
+    .filter(|s| !s.is_empty())
+    .map(|s| s.split_once('=').unwrap_or((s, "")))
+    .map(|(k, v)| {
+      (
+        decode_uri_component(&k.replace('+', " ")),
+        decode_uri_component(&v.replace('+', " ")),
+      )
+    })
+    .collect();
+
+  Request {
+    method: request["method"].as_str().unwrap_or("GET").to_uppercase(),
+    path: path.to_string(),
+    query: query.to_string(),
+    version: String::from("HTTP/1.1"),
+    headers: HashMap::new(),
+    body: request["postData"]["text"]
+      .as_str()
+      .unwrap_or("")
+      .as_bytes()
+      .to_vec(),
+    queries,
+    params: HashMap::new(),
+    matches: Vec::new(),
+  }
+}
+
+/// Returns a recorded response, unless its base64 body is invalid. The body of
+/// a HAR file is already decoded, so the headers that describe the encoding on
+/// the wire are dropped along with the stripped ones.
+fn recorded_response(response: &Value, status: u16, strip: &[&str]) -> Option<Response> {
+  let mut headers: HashMap<String, String> = HashMap::new();
+  for header in response["headers"].as_array().into_iter().flatten() {
+    let (name, value) = match (header["name"].as_str(), header["value"].as_str()) {
+      (Some(name), Some(value)) => (name, value),
+      _ => continue,
+    };
+    let dropped = name.starts_with(':')
+      || client::is_hop_by_hop(name)
+      || name.eq_ignore_ascii_case("Content-Encoding")
+      || strip.iter().any(|s| name.eq_ignore_ascii_case(s));
+    if !dropped {
+      insert_header(&mut headers, name, value);
+    }
+  }
+
+  let content = &response["content"];
+  let mime_type = content["mimeType"].as_str().unwrap_or("");
+  let has_type = headers
+    .keys()
+    .any(|name| name.eq_ignore_ascii_case("Content-Type"));
+  if !has_type
+    && !mime_type.is_empty()
+    && !strip.iter().any(|s| s.eq_ignore_ascii_case("Content-Type"))
+  {
+    headers.insert(String::from("Content-Type"), mime_type.to_string());
+  }
+
+  let text = content["text"].as_str().unwrap_or("");
+  let body = if content["encoding"] == "base64" {
+    base64::decode(text)?
+  } else {
+    text.as_bytes().to_vec()
+  };
+
+  Some(Response {
+    status,
+    body,
+    headers,
+    fault: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ProjectConfig;
+
+  fn entry(method: &str, url: &str, status: u16, text: &str, wait: f64) -> Value {
+    json!({
+      "request": { "method": method, "url": url, "headers": [] },
+      "response": {
+        "status": status,
+        "headers": [
+          { "name": "Content-Type", "value": "application/json" },
+          { "name": "Date", "value": "Mon, 01 Jan 2024 00:00:00 GMT" },
+          { "name": "Set-Cookie", "value": "id=1" },
+          { "name": "Content-Encoding", "value": "gzip" },
+        ],
+        "content": { "mimeType": "application/json", "text": text },
+      },
+      "time": 100,
+      "timings": { "wait": wait },
+    })
+  }
+
+  #[test]
+  fn groups_entries_by_path_and_method() {
+    let har = json!({ "log": { "entries": [
+      entry("GET", "https://api.test/users/1?full=1", 200, r#"{"id":1}"#, 20.0),
+      entry("GET", "https://api.test/users/1?full=1", 200, r#"{"id":1,"v":2}"#, 40.0),
+      entry("DELETE", "https://api.test/users/1", 204, "", 10.4),
+      entry("GET", "https://api.test/blocked", 0, "", -1.0),
+      entry("GET", "data:image/png;base64,AA", 200, "", 0.0),
+    ]}});
+
+    let config = import(har.to_string().as_bytes(), &["Date", "set-cookie"]).unwrap();
+    let endpoints = config["endpoints"].as_array().unwrap();
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(endpoints[0]["path"], "users/1");
+
+    let get = &endpoints[0]["when"][0];
+    assert_eq!(get["request"], json!({ "queries": { "full": "1" } }));
+    assert_eq!(get["delay"], 30);
+    assert_eq!(
+      get["responses"],
+      json!([
+        { "status": 200, "headers": { "Content-Type": "application/json" }, "body": { "id": 1 } },
+        { "status": 200, "headers": { "Content-Type": "application/json" }, "body": { "id": 1, "v": 2 } },
+      ])
+    );
+    let delete = &endpoints[0]["when"][1];
+    assert_eq!(delete["method"], "DELETE");
+    assert_eq!(delete["response"]["status"], 204);
+    assert_eq!(delete["delay"], 10);
+
+    assert!(ProjectConfig::from_value(&config).is_ok());
+  }
+
+  #[test]
+  fn clamps_slow_responses_to_the_longest_delay() {
+    let har = json!({ "log": { "entries": [
+      entry("GET", "https://api.test/slow", 200, "{}", 5e9),
+    ]}});
+
+    let config = import(har.to_string().as_bytes(), &[]).unwrap();
+    assert_eq!(config["endpoints"][0]["when"][0]["delay"], MAX_DELAY);
+    assert!(ProjectConfig::from_value(&config).is_ok());
+  }
+
+  #[test]
+  fn keeps_binary_bodies_and_reports_problems() {
+    let mut image = entry("GET", "http://cdn.test/logo.png", 200, "iVBORw0=", 0.0);
+    image["response"]["content"] =
+      json!({ "mimeType": "image/png", "text": "iVBORw0=", "encoding": "base64" });
+    image["response"]["headers"] = json!([]);
+    let config = import(
+      json!({ "log": { "entries": [image] } })
+        .to_string()
+        .as_bytes(),
+      &[],
+    )
+    .unwrap();
+    let response = &config["endpoints"][0]["when"][0]["response"];
+    assert_eq!(response["headers"], json!({ "Content-Type": "image/png" }));
+    assert_eq!(response["bodyBase64"], "iVBORw0=");
+
+    let problems = import(br#"{ "log": { "entries": [{ "request": {} }] } }"#, &[]).unwrap_err();
+    assert_eq!(problems[0].pointer, "/log/entries/0/request/url");
+
+    let har = json!({ "log": { "entries": [
+      entry("GET", "https://api.test/users/1", 200, "{}", 0.0),
+      entry("GET", "https://api.test/users/:id(", 200, "{}", 0.0),
+      entry("GET", "https://api.test/files/**", 200, "{}", 0.0),
+    ]}});
+    let problems = import(har.to_string().as_bytes(), &[]).unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(
+      pointers,
+      ["/log/entries/1/request/url", "/log/entries/2/request/url"]
+    );
+  }
+}
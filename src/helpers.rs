@@ -1,14 +1,103 @@
-use std::path::PathBuf;
+use serde_json::{json, Value};
+use std::{collections::HashMap, path::PathBuf};
 
-use crate::web_server::types::Request;
+use crate::{
+  config::Problem,
+  web_server::types::{Request, Response},
+};
+
+/// Returns the directory of the projects' config files.
+pub fn get_projects_dir() -> PathBuf {
+  PathBuf::from("database/projects")
+}
 
 /// Returns the path to a project's config file.
 pub fn get_project_config_file_path(project_name: &str) -> PathBuf {
-  PathBuf::from(format!("database/projects/{}.json", project_name))
+  get_projects_dir().join(format!("{}.json", project_name))
 }
 
-/// Returns the path to a project's config file from a request.
-pub fn config_file_path_from_request(request: &Request) -> PathBuf {
-  let project_name = request.params.get("name").unwrap();
-  get_project_config_file_path(project_name)
+/// Returns the directory of a project's files, such as the ones served by
+/// `bodyFile`.
+pub fn get_project_dir(project_name: &str) -> PathBuf {
+  get_projects_dir().join(project_name)
+}
+
+/// Returns whether a name can be used as the file name of a project, without
+/// reaching outside of the projects' directory.
+pub fn is_valid_project_name(name: &str) -> bool {
+  !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// Returns the name of the project a request is about, or a `400` response
+/// when it is not a valid one.
+pub fn project_name(request: &Request) -> Result<&str, Response> {
+  match request.params.get("name") {
+    Some(name) if is_valid_project_name(name) => Ok(name),
+    _ => Err(json_response(
+      400,
+      &json!({ "error": "Invalid project name." }),
+    )),
+  }
+}
+
+/// Returns a response with a JSON body.
+pub fn json_response(status: u16, body: &Value) -> Response {
+  let mut headers = HashMap::new();
+  headers.insert(
+    String::from("Content-Type"),
+    String::from("application/json"),
+  );
+
+  Response {
+    status,
+    body: body.to_string().into_bytes(),
+    headers,
+    fault: None,
+  }
+}
+
+/// Returns a response that lists the problems of a project's config.
+pub fn problems_response(status: u16, error: &str, problems: &[Problem]) -> Response {
+  let problems: Vec<Value> = problems
+    .iter()
+    .map(|p| json!({ "pointer": p.pointer, "message": p.message }))
+    .collect();
+
+  json_response(status, &json!({ "error": error, "problems": problems }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rejects_project_names_outside_of_the_projects_directory() {
+    for name in ["", ".", "..", "../..", ".hidden", "a/b", "a\\b"] {
+      assert!(!is_valid_project_name(name), "accepted {:?}", name);
+    }
+    for name in ["demo", "my-project", "v1.2"] {
+      assert!(is_valid_project_name(name), "rejected {:?}", name);
+    }
+  }
+
+  #[test]
+  fn answers_400_to_invalid_project_names() {
+    let mut request = Request {
+      method: String::from("DELETE"),
+      path: String::from("/projects/..%2F.."),
+      query: String::new(),
+      version: String::from("HTTP/1.1"),
+      headers: HashMap::new(),
+      body: Vec::new(),
+      queries: HashMap::new(),
+      params: HashMap::from([(String::from("name"), String::from("../.."))]),
+      matches: Vec::new(),
+    };
+    assert_eq!(project_name(&request).unwrap_err().status, 400);
+
+    request
+      .params
+      .insert(String::from("name"), String::from("demo"));
+    assert_eq!(project_name(&request).ok(), Some("demo"));
+  }
 }
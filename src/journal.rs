@@ -0,0 +1,364 @@
+use crate::{base64, template, web_server::types::PathPattern};
+use serde_json::{json, Value};
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Mutex,
+  time::{Duration, SystemTime},
+};
+
+/// An in-memory record of the mock requests a server received, so that tests
+/// can assert what their application actually sent.
+///
+/// The journal keeps at most `capacity` entries per project, so that a busy
+/// project does not push out the requests of the others, and at most `budget`
+/// bytes in all, taken from the largest project first. The oldest entries of a
+/// project are forgotten first.
+pub struct Journal {
+  capacity: usize,
+  budget: usize,
+  state: Mutex<State>,
+}
+
+struct State {
+  next_id: u64,
+  /// The size of every entry, as counted by `Entry::size`.
+  bytes: usize,
+  projects: HashMap<String, Log>,
+}
+
+/// The entries of a project, along with how many were forgotten since the
+/// project's entries were last cleared.
+#[derive(Default)]
+struct Log {
+  entries: VecDeque<Entry>,
+  bytes: usize,
+  evicted: u64,
+}
+
+impl Log {
+  /// Forgets the oldest entry and returns its size.
+  fn evict(&mut self) -> usize {
+    let size = self.entries.pop_front().map_or(0, |entry| entry.size());
+    self.bytes -= size;
+    self.evicted += 1;
+    size
+  }
+}
+
+/// What an entry is counted to take besides its data.
+const ENTRY_OVERHEAD: usize = 256;
+
+/// A received request and how it was answered.
+#[derive(Debug, Clone)]
+pub struct Entry {
+  /// Assigned by the journal when the entry is recorded.
+  pub id: u64,
+  pub project: String,
+  pub method: String,
+  /// The path within the project, e.g. `users/1`.
+  pub path: String,
+  pub queries: HashMap<String, String>,
+  pub headers: HashMap<String, String>,
+  pub body: Vec<u8>,
+  pub matched: Option<Matched>,
+  pub status: u16,
+  pub received_at: SystemTime,
+  pub duration: Duration,
+}
+
+/// The condition that answered a request.
+#[derive(Debug, Clone)]
+pub struct Matched {
+  pub endpoint: String,
+  pub condition: usize,
+}
+
+/// Selects entries by project, path and method. Missing criteria match
+/// every entry.
+#[derive(Default)]
+pub struct Filter {
+  pub project: Option<String>,
+  /// A path pattern, like the ones of endpoints, e.g. `users/:id`.
+  pub path: Option<PathPattern>,
+  pub method: Option<String>,
+}
+
+impl Filter {
+  fn accepts(&self, entry: &Entry) -> bool {
+    self.project.as_ref().is_none_or(|p| *p == entry.project)
+      && self
+        .method
+        .as_ref()
+        .is_none_or(|m| m.eq_ignore_ascii_case(&entry.method))
+      && self
+        .path
+        .as_ref()
+        .is_none_or(|p| p.matches(&entry.path).is_some())
+  }
+}
+
+impl Journal {
+  pub fn new(capacity: usize, budget: usize) -> Journal {
+    Journal {
+      capacity,
+      budget,
+      state: Mutex::new(State {
+        next_id: 1,
+        bytes: 0,
+        projects: HashMap::new(),
+      }),
+    }
+  }
+
+  /// Records an entry and returns the id it was given.
+  pub fn record(&self, mut entry: Entry) -> u64 {
+    let mut state = self.state.lock().unwrap();
+    let State {
+      next_id,
+      bytes,
+      projects,
+    } = &mut *state;
+    entry.id = *next_id;
+    *next_id += 1;
+
+    let id = entry.id;
+    let size = entry.size();
+    let log = projects.entry(entry.project.clone()).or_default();
+    if self.capacity == 0 {
+      log.evicted += 1;
+      return id;
+    }
+    while log.entries.len() >= self.capacity {
+      *bytes -= log.evict();
+    }
+    log.entries.push_back(entry);
+    log.bytes += size;
+    *bytes += size;
+
+    while *bytes > self.budget {
+      let largest = projects.values_mut().max_by_key(|log| log.bytes).unwrap();
+      *bytes -= largest.evict();
+    }
+
+    id
+  }
+
+  /// Returns the entries the filter accepts, oldest first.
+  pub fn list(&self, filter: &Filter) -> Vec<Entry> {
+    let state = self.state.lock().unwrap();
+
+    let mut entries: Vec<Entry> = state
+      .projects
+      .values()
+      .flat_map(|log| &log.entries)
+      .filter(|entry| filter.accepts(entry))
+      .cloned()
+      .collect();
+    entries.sort_by_key(|entry| entry.id);
+    entries
+  }
+
+  pub fn get(&self, id: u64) -> Option<Entry> {
+    let state = self.state.lock().unwrap();
+
+    state
+      .projects
+      .values()
+      .flat_map(|log| &log.entries)
+      .find(|entry| entry.id == id)
+      .cloned()
+  }
+
+  /// Returns how many entries of a project were forgotten to make room for
+  /// newer ones since its entries were last cleared.
+  pub fn evicted(&self, project: &str) -> u64 {
+    let state = self.state.lock().unwrap();
+
+    state.projects.get(project).map_or(0, |log| log.evicted)
+  }
+
+  /// Forgets the entries the filter accepts and returns how many there were.
+  /// Clearing every entry of a project also resets its count of forgotten
+  /// ones.
+  pub fn clear(&self, filter: &Filter) -> usize {
+    let mut state = self.state.lock().unwrap();
+    let State {
+      bytes, projects, ..
+    } = &mut *state;
+    let whole_projects = filter.path.is_none() && filter.method.is_none();
+    let mut cleared = 0;
+
+    projects.retain(|project, log| {
+      if filter.project.as_ref().is_some_and(|p| p != project) {
+        return true;
+      }
+      let len = log.entries.len();
+      log.entries.retain(|entry| {
+        if !filter.accepts(entry) {
+          return true;
+        }
+        log.bytes -= entry.size();
+        *bytes -= entry.size();
+        false
+      });
+      cleared += len - log.entries.len();
+      if whole_projects {
+        log.evicted = 0;
+      }
+      !log.entries.is_empty() || log.evicted > 0
+    });
+
+    cleared
+  }
+}
+
+impl Entry {
+  /// Returns roughly how many bytes the entry takes in memory.
+  fn size(&self) -> usize {
+    let fields =
+      |map: &HashMap<String, String>| map.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>();
+
+    ENTRY_OVERHEAD
+      + self.project.len()
+      + self.method.len()
+      + self.path.len()
+      + fields(&self.queries)
+      + fields(&self.headers)
+      + self.body.len()
+  }
+
+  /// Describes the entry as JSON. A body that is not UTF-8 text is given as
+  /// `bodyBase64` instead of `body`.
+  pub fn to_json(&self) -> Value {
+    let mut value = json!({
+      "id": self.id,
+      "project": self.project,
+      "method": self.method,
+      "path": self.path,
+      "queries": self.queries,
+      "headers": self.headers,
+      "matched": self.matched.as_ref().map(|m| json!({
+        "endpoint": m.endpoint,
+        "condition": m.condition,
+      })),
+      "status": self.status,
+      "receivedAt": template::format_rfc3339(self.received_at),
+      "durationMs": self.duration.as_millis() as u64,
+    });
+
+    match std::str::from_utf8(&self.body) {
+      Ok(body) => value["body"] = json!(body),
+      Err(_) => value["bodyBase64"] = json!(base64::encode(&self.body)),
+    }
+
+    value
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(project: &str, method: &str, path: &str) -> Entry {
+    Entry {
+      id: 0,
+      project: project.to_string(),
+      method: method.to_string(),
+      path: path.to_string(),
+      queries: HashMap::new(),
+      headers: HashMap::new(),
+      body: Vec::new(),
+      matched: None,
+      status: 200,
+      received_at: SystemTime::now(),
+      duration: Duration::ZERO,
+    }
+  }
+
+  fn ids(entries: Vec<Entry>) -> Vec<u64> {
+    entries.iter().map(|entry| entry.id).collect()
+  }
+
+  #[test]
+  fn forgets_the_oldest_entries_first() {
+    let journal = Journal::new(2, usize::MAX);
+    for path in ["a", "b", "c"] {
+      journal.record(entry("demo", "GET", path));
+    }
+
+    journal.record(entry("other", "GET", "d"));
+
+    assert_eq!(ids(journal.list(&Filter::default())), [2, 3, 4]);
+    assert!(journal.get(1).is_none());
+    assert_eq!(journal.get(3).unwrap().path, "c");
+    assert_eq!(journal.evicted("demo"), 1);
+    assert_eq!(journal.evicted("other"), 0);
+
+    let filter = Filter {
+      project: Some("demo".to_string()),
+      ..Filter::default()
+    };
+    journal.clear(&filter);
+    assert_eq!(journal.evicted("demo"), 0);
+  }
+
+  #[test]
+  fn forgets_entries_of_the_largest_project_over_the_budget() {
+    let mut large = entry("large", "POST", "upload");
+    large.body = vec![0; 1000];
+    let journal = Journal::new(10, 3 * large.size());
+    journal.record(entry("small", "GET", "a"));
+    for _ in 0..3 {
+      journal.record(large.clone());
+    }
+
+    assert_eq!(ids(journal.list(&Filter::default())), [1, 3, 4]);
+    assert_eq!(journal.evicted("large"), 1);
+    assert_eq!(journal.evicted("small"), 0);
+
+    journal.clear(&Filter::default());
+    for _ in 0..3 {
+      journal.record(large.clone());
+    }
+    assert_eq!(journal.list(&Filter::default()).len(), 3);
+  }
+
+  #[test]
+  fn filters_entries() {
+    let journal = Journal::new(10, usize::MAX);
+    journal.record(entry("demo", "GET", "users/1"));
+    journal.record(entry("demo", "POST", "users"));
+    journal.record(entry("other", "GET", "users/2"));
+
+    let filter = Filter {
+      path: Some(PathPattern::parse("users/:id").unwrap()),
+      ..Filter::default()
+    };
+    assert_eq!(ids(journal.list(&filter)), [1, 3]);
+
+    let filter = Filter {
+      project: Some("demo".to_string()),
+      method: Some("post".to_string()),
+      ..Filter::default()
+    };
+    assert_eq!(ids(journal.list(&filter)), [2]);
+
+    let filter = Filter {
+      project: Some("demo".to_string()),
+      ..Filter::default()
+    };
+    assert_eq!(journal.clear(&filter), 2);
+    assert_eq!(ids(journal.list(&Filter::default())), [3]);
+  }
+
+  #[test]
+  fn describes_binary_bodies_in_base64() {
+    let mut binary = entry("demo", "POST", "upload");
+    binary.body = vec![0, 255];
+    let value = binary.to_json();
+
+    assert_eq!(value["bodyBase64"], "AP8=");
+    assert!(value.get("body").is_none());
+    assert_eq!(entry("demo", "GET", "a").to_json()["body"], "");
+  }
+}
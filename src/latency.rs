@@ -0,0 +1,141 @@
+use crate::{config::Delay, random::Rng};
+use std::{f64::consts::PI, time::Duration};
+
+/// The longest delay, in milliseconds, which bounds both the parameters of the
+/// distributions and their draws: an hour.
+pub const MAX_DELAY: u64 = 3_600_000;
+
+/// Draws a delay from its distribution. Draws are clamped between zero and
+/// `MAX_DELAY`, which the long tail of a log-normal distribution may exceed.
+pub fn sample(delay: &Delay, rng: &mut Rng) -> Duration {
+  let millis = match delay {
+    Delay::Fixed(millis) => return Duration::from_millis(*millis),
+    Delay::Uniform { min, max } => min + (max - min) * rng.next_f64(),
+    Delay::Normal { mean, stddev } => mean + stddev * standard_normal(rng),
+    Delay::LogNormal { mean, stddev } => {
+      // The parameters of the underlying normal distribution that give the
+      // log-normal one the requested mean and standard deviation.
+      let variance = (1.0 + (stddev * stddev) / (mean * mean)).ln();
+      let mu = mean.ln() - variance / 2.0;
+      (mu + variance.sqrt() * standard_normal(rng)).exp()
+    }
+    Delay::Percentiles(targets) => percentile(targets, rng.next_f64()),
+  };
+
+  let millis = millis.clamp(0.0, MAX_DELAY as f64);
+  Duration::try_from_secs_f64(millis / 1000.0).unwrap_or(Duration::ZERO)
+}
+
+/// Returns the delay at a fraction of the distribution, interpolating linearly
+/// between the targets. The implicit 0th percentile is no delay at all, and the
+/// highest target is never exceeded.
+fn percentile(targets: &[(f64, f64)], fraction: f64) -> f64 {
+  let mut previous = (0.0, 0.0);
+
+  for &(target, millis) in targets {
+    if fraction <= target {
+      let share = (fraction - previous.0) / (target - previous.0);
+      return previous.1 + (millis - previous.1) * share;
+    }
+    previous = (target, millis);
+  }
+
+  previous.1
+}
+
+/// Draws from the standard normal distribution, with the Box-Muller transform.
+fn standard_normal(rng: &mut Rng) -> f64 {
+  // The first number must not be zero, whose logarithm is infinite.
+  let u1 = 1.0 - rng.next_f64();
+  let u2 = rng.next_f64();
+
+  (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn samples(delay: &Delay, count: usize) -> Vec<f64> {
+    let mut rng = Rng::seeded(3);
+    let mut samples: Vec<f64> = (0..count)
+      .map(|_| sample(delay, &mut rng).as_secs_f64() * 1000.0)
+      .collect();
+    samples.sort_by(f64::total_cmp);
+    samples
+  }
+
+  fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+  }
+
+  #[test]
+  fn uniform_delays_stay_in_range() {
+    let samples = samples(
+      &Delay::Uniform {
+        min: 10.0,
+        max: 20.0,
+      },
+      1000,
+    );
+
+    assert!(samples[0] >= 10.0 && samples[999] < 20.0);
+    assert!((mean(&samples) - 15.0).abs() < 0.5);
+  }
+
+  #[test]
+  fn distributions_have_the_requested_mean() {
+    let normal = samples(
+      &Delay::Normal {
+        mean: 100.0,
+        stddev: 10.0,
+      },
+      5000,
+    );
+    assert!((mean(&normal) - 100.0).abs() < 1.0);
+
+    let log_normal = samples(
+      &Delay::LogNormal {
+        mean: 100.0,
+        stddev: 50.0,
+      },
+      5000,
+    );
+    assert!((mean(&log_normal) - 100.0).abs() < 3.0);
+    // The long tail lies above the mean.
+    assert!(log_normal[2500] < 100.0);
+  }
+
+  #[test]
+  fn percentile_targets_are_met() {
+    let samples = samples(&Delay::Percentiles(vec![(0.5, 100.0), (0.9, 300.0)]), 1000);
+
+    assert!((samples[499] - 100.0).abs() < 10.0);
+    assert!((samples[899] - 300.0).abs() < 20.0);
+    assert_eq!(samples[999], 300.0);
+  }
+
+  #[test]
+  fn draws_never_exceed_the_longest_delay() {
+    let samples = samples(
+      &Delay::LogNormal {
+        mean: MAX_DELAY as f64,
+        stddev: MAX_DELAY as f64,
+      },
+      1000,
+    );
+
+    assert!(samples[0] >= 0.0);
+    assert_eq!(samples[999], MAX_DELAY as f64);
+  }
+
+  #[test]
+  fn seeded_samples_repeat() {
+    let delay = Delay::Normal {
+      mean: 50.0,
+      stddev: 20.0,
+    };
+
+    assert_eq!(samples(&delay, 10), samples(&delay, 10));
+  }
+}
@@ -1,20 +1,52 @@
 mod web_server;
 
-use std::{collections::HashMap, fs::read_to_string};
+use cache::ProjectCache;
+use journal::Journal;
+use scenarios::Scenarios;
+use sequences::Sequences;
+use std::{collections::HashMap, env, fs::read_to_string, process, sync::Arc};
 use web_server::{
   types::{Method, Nested, RequestOption, Response},
   Server, ServerConf,
 };
 
+mod base64;
+mod cache;
+mod cli;
+mod config;
 mod handlers;
+mod har;
 mod helpers;
+mod journal;
+mod latency;
+mod matcher;
+mod openapi;
+mod proxy;
+mod random;
+mod scenarios;
+mod sequences;
+mod template;
 
 const SERVER_ADDR: &str = "127.0.0.1:53500";
 const MAX_CONNECTIONS: usize = 1000;
+/// How many mock requests of each project the journal remembers.
+const JOURNAL_CAPACITY: usize = 1000;
+/// How many bytes of mock requests the journal remembers in all.
+const JOURNAL_BUDGET: usize = 64 * 1024 * 1024;
 
 fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if !args.is_empty() {
+    process::exit(cli::run(&args));
+  }
+
+  let cache = Arc::new(ProjectCache::new());
+  let journal = Arc::new(Journal::new(JOURNAL_CAPACITY, JOURNAL_BUDGET));
+  let scenarios = Arc::new(Scenarios::new());
+  let sequences = Arc::new(Sequences::new());
   let mut server = Server::new(ServerConf {
     max_connections: MAX_CONNECTIONS,
+    ..ServerConf::default()
   });
 
   server.get("/", |_| {
@@ -26,9 +58,16 @@ fn main() {
     Response::json(status, body, headers)
   });
 
+  // List the projects.
+  server.get("/projects", handlers::list_projects(cache.clone()));
+
   // Get a project.
   server.get("/projects/:name", |request| {
-    let file = helpers::config_file_path_from_request(&request);
+    let name = match helpers::project_name(&request) {
+      Ok(name) => name,
+      Err(response) => return response,
+    };
+    let file = helpers::get_project_config_file_path(name);
 
     if file.exists() {
       let content = read_to_string(file).unwrap();
@@ -46,17 +85,103 @@ fn main() {
   });
 
   // Create a project.
-  server.post("/projects/:name", handlers::save_config());
+  server.post(
+    "/projects/:name",
+    handlers::save_config(cache.clone(), sequences.clone()),
+  );
 
   // Update a project.
-  server.put("/projects/:name", handlers::save_config());
+  server.put(
+    "/projects/:name",
+    handlers::save_config(cache.clone(), sequences.clone()),
+  );
+
+  // Merge endpoints into a project.
+  server.patch(
+    "/projects/:name",
+    handlers::patch_config(cache.clone(), sequences.clone()),
+  );
+
+  // Delete a project.
+  server.delete(
+    "/projects/:name",
+    handlers::delete_config(cache.clone(), scenarios.clone(), sequences.clone()),
+  );
+
+  // Generate a project from an OpenAPI 3 document. Like the other routes under
+  // a project, it has to be registered before the mocks.
+  server.post(
+    "/projects/:name/import/openapi",
+    handlers::import_openapi(cache.clone(), sequences.clone()),
+  );
+  server.put(
+    "/projects/:name/import/openapi",
+    handlers::import_openapi(cache.clone(), sequences.clone()),
+  );
+
+  // Generate a project from the requests recorded in a HAR file.
+  server.post(
+    "/projects/:name/import/har",
+    handlers::import_har(cache.clone(), sequences.clone()),
+  );
+  server.put(
+    "/projects/:name/import/har",
+    handlers::import_har(cache.clone(), sequences.clone()),
+  );
+
+  // Describe a project as an OpenAPI 3 document.
+  server.get(
+    "/projects/:name/export/openapi",
+    handlers::export_openapi(cache.clone()),
+  );
+
+  // The requests received by the mocks.
+  server.get(
+    "/__admin/requests",
+    handlers::list_requests(journal.clone()),
+  );
+  server.get(
+    "/__admin/requests/:id",
+    handlers::get_request(journal.clone()),
+  );
+  server.delete(
+    "/__admin/requests",
+    handlers::clear_requests(journal.clone()),
+  );
+
+  // Check the requests received by a project's mocks. It has to be registered
+  // before the mocks, which would answer it otherwise.
+  server.post(
+    "/projects/:name/__verify",
+    handlers::verify_requests(journal.clone()),
+  );
+
+  // Read and reset the states of a project's scenarios.
+  server.get(
+    "/projects/:name/__scenarios",
+    handlers::list_scenarios(cache.clone(), scenarios.clone()),
+  );
+  server.put(
+    "/projects/:name/__scenarios/:scenario",
+    handlers::set_scenario(cache.clone(), scenarios.clone()),
+  );
+  server.delete(
+    "/projects/:name/__scenarios",
+    handlers::reset_scenarios(scenarios.clone()),
+  );
+
+  // Start the response sequences of a project's conditions over.
+  server.delete(
+    "/projects/:name/__sequences",
+    handlers::reset_sequences(sequences.clone()),
+  );
 
   // A mock request of a given project.
   server.request(
-    handlers::mock_request(),
+    handlers::mock_request(cache, journal, scenarios, sequences),
     RequestOption {
       path: web_server::types::RequestPathPattern::Match(r"^/projects/([^/]+)/([^?]+)".to_string()),
-      method: Method::Get,
+      method: Method::Any,
     },
   );
 
@@ -0,0 +1,719 @@
+use crate::{
+  config::{BodyRules, Condition, Operator, ProjectConfig, RequestRules, Rule, Verification},
+  journal::Entry,
+  scenarios::{self, States},
+  web_server::types::Request,
+};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+impl Operator {
+  /// Tests an actual value against an expected value. A missing actual value
+  /// only satisfies the negated operators.
+  pub fn test(&self, expected: &Value, actual: Option<&Value>) -> bool {
+    match (self, actual) {
+      (Operator::Is, Some(actual)) => equals(expected, actual),
+      (Operator::IsNot, Some(actual)) => !equals(expected, actual),
+      (Operator::Contains, Some(actual)) => contains(expected, actual),
+      (Operator::NotContains, Some(actual)) => !contains(expected, actual),
+      (Operator::Is | Operator::Contains, None) => false,
+      (Operator::IsNot | Operator::NotContains, None) => true,
+    }
+  }
+}
+
+/// The condition chosen to answer a request, by its indexes in the config.
+pub struct Selection {
+  pub endpoint: usize,
+  pub condition: usize,
+  /// The parameters captured by the endpoint's path.
+  pub params: HashMap<String, String>,
+}
+
+/// Finds the condition that answers a request to a path of a project, given
+/// the current states of its scenarios.
+///
+/// When several conditions match, the winner is the one with the highest
+/// `priority`, then a `HEAD` condition over a `GET` one for a `HEAD` request,
+/// then the one whose endpoint's path is the most specific, then the one with
+/// the most rules. Remaining ties go to the first one declared.
+pub fn select(
+  config: &ProjectConfig,
+  path: &str,
+  request: &mut Request,
+  states: &States,
+) -> Option<Selection> {
+  let mut best: Option<(Selection, Rank)> = None;
+
+  for (i, endpoint) in config.endpoints.iter().enumerate() {
+    // The parameters captured by the path can be matched like the rest of the
+    // request.
+    match endpoint.pattern.matches(path) {
+      Some(params) => request.params = params,
+      None => continue,
+    }
+
+    for (j, condition) in endpoint.when.iter().enumerate() {
+      if !method_matches(&condition.method, &request.method)
+        || state_mismatch(condition, states).is_some()
+        || !matches(&condition.request, request)
+      {
+        continue;
+      }
+
+      let rank = (
+        condition.priority,
+        condition.method == request.method,
+        endpoint.pattern.specificity(),
+        condition.request.len(),
+      );
+      if best.as_ref().is_none_or(|(_, best_rank)| rank > *best_rank) {
+        let selection = Selection {
+          endpoint: i,
+          condition: j,
+          params: request.params.clone(),
+        };
+        best = Some((selection, rank));
+      }
+    }
+  }
+
+  best.map(|(selection, _)| selection)
+}
+
+/// The priority, whether the method is the request's own rather than `GET` for
+/// a `HEAD` request, path specificity and number of rules of a condition.
+type Rank = (i64, bool, Vec<u8>, usize);
+
+/// Returns whether a condition's method accepts a request's. A `HEAD` request
+/// falls back to the `GET` conditions, which answer it without their body.
+fn method_matches(condition: &str, request: &str) -> bool {
+  condition == request || (condition == "GET" && request == "HEAD")
+}
+
+/// A rule that a request failed.
+pub struct Mismatch {
+  /// What the rule is about: `method`, `path`, `state`, `params`, `queries`,
+  /// `headers` or `body`.
+  pub rule: &'static str,
+  pub operator: Option<Operator>,
+  pub name: Option<String>,
+  pub expected: Value,
+  /// The request's value, or `null` when it does not have one.
+  pub actual: Value,
+}
+
+impl Mismatch {
+  fn new(rule: &'static str, expected: Value, actual: Value) -> Mismatch {
+    Mismatch {
+      rule,
+      operator: None,
+      name: None,
+      expected,
+      actual,
+    }
+  }
+}
+
+/// A condition that did not match a request, along with the rules it failed.
+pub struct NearMiss {
+  pub endpoint: usize,
+  pub condition: usize,
+  pub mismatches: Vec<Mismatch>,
+}
+
+/// Lists the conditions closest to matching a request, which are the ones that
+/// failed the fewest rules, preferring the ones whose path matched.
+pub fn diagnose(
+  config: &ProjectConfig,
+  path: &str,
+  request: &mut Request,
+  states: &States,
+  limit: usize,
+) -> Vec<NearMiss> {
+  let mut near_misses = Vec::new();
+
+  for (i, endpoint) in config.endpoints.iter().enumerate() {
+    let path_params = endpoint.pattern.matches(path);
+    request.params = path_params.clone().unwrap_or_default();
+
+    for (j, condition) in endpoint.when.iter().enumerate() {
+      let mut mismatches = Vec::new();
+      if path_params.is_none() {
+        mismatches.push(Mismatch::new(
+          "path",
+          Value::String(endpoint.path.clone()),
+          Value::String(path.to_string()),
+        ));
+      }
+      if !method_matches(&condition.method, &request.method) {
+        mismatches.push(Mismatch::new(
+          "method",
+          Value::String(condition.method.clone()),
+          Value::String(request.method.clone()),
+        ));
+      }
+      mismatches.extend(state_mismatch(condition, states));
+      // The parameters can only be checked when the path matched.
+      for mismatch in mismatches_of(&condition.request, request) {
+        if path_params.is_some() || mismatch.rule != "params" {
+          mismatches.push(mismatch);
+        }
+      }
+
+      near_misses.push(NearMiss {
+        endpoint: i,
+        condition: j,
+        mismatches,
+      });
+    }
+  }
+
+  // The sort is stable, so ties keep the order the conditions are declared in.
+  near_misses.sort_by_key(|n| {
+    (
+      n.mismatches.iter().any(|m| m.rule == "path"),
+      n.mismatches.len(),
+    )
+  });
+  near_misses.truncate(limit);
+  near_misses
+}
+
+/// Returns the mismatch of a condition that requires its scenario to be in
+/// another state than the current one.
+fn state_mismatch(condition: &Condition, states: &States) -> Option<Mismatch> {
+  let (scenario, required) = (
+    condition.scenario.as_ref()?,
+    condition.required_state.as_ref()?,
+  );
+  let state = scenarios::state_of(states, scenario);
+  if state == required {
+    return None;
+  }
+
+  Some(Mismatch {
+    rule: "state",
+    operator: Some(Operator::Is),
+    name: Some(scenario.clone()),
+    expected: Value::String(required.clone()),
+    actual: Value::String(state.to_string()),
+  })
+}
+
+/// Returns whether a recorded request matches the method, path and rules of a
+/// verification.
+pub fn verifies(verification: &Verification, entry: &Entry) -> bool {
+  if verification
+    .method
+    .as_ref()
+    .is_some_and(|method| !method.eq_ignore_ascii_case(&entry.method))
+  {
+    return false;
+  }
+  // Like for a condition, the rules may refer to the parameters of the path.
+  let params = match &verification.path {
+    Some(pattern) => match pattern.matches(&entry.path) {
+      Some(params) => params,
+      None => return false,
+    },
+    None => HashMap::new(),
+  };
+
+  let request = Request {
+    method: entry.method.clone(),
+    path: entry.path.clone(),
+    query: String::new(),
+    version: String::from("HTTP/1.1"),
+    headers: entry.headers.clone(),
+    body: entry.body.clone(),
+    queries: entry.queries.clone(),
+    params,
+    matches: Vec::new(),
+  };
+  matches(&verification.request, &request)
+}
+
+/// Returns whether a request satisfies the `request` block of a condition. An
+/// empty block matches every request. It stops at the first rule that fails,
+/// whereas `mismatches_of` lists them all for the near misses.
+pub fn matches(rules: &RequestRules, request: &Request) -> bool {
+  passes(&rules.params, |name| {
+    request.params.get(name).map(|v| Value::String(v.clone()))
+  }) && passes(&rules.queries, |name| {
+    request.queries.get(name).map(|v| Value::String(v.clone()))
+  }) && passes(&rules.headers, |name| {
+    request.header(name).map(|v| Value::String(v.to_string()))
+  }) && rules
+    .body
+    .as_ref()
+    .is_none_or(|body| body_matches(body, request))
+}
+
+fn passes<F>(rules: &[Rule], actual_of: F) -> bool
+where
+  F: Fn(&str) -> Option<Value>,
+{
+  rules.iter().all(|rule| {
+    rule
+      .operator
+      .test(&rule.value, actual_of(&rule.name).as_ref())
+  })
+}
+
+fn body_matches(rules: &BodyRules, request: &Request) -> bool {
+  let body = parse_body(request);
+
+  match rules {
+    BodyRules::Partial(expected) => {
+      matches!(&body, Value::Object(actual) if is_subset(expected, actual))
+    }
+    BodyRules::Rules(rules) => passes(rules, |name| body_field(&body, name)),
+  }
+}
+
+/// Returns the rules of a `request` block that a request fails.
+fn mismatches_of(rules: &RequestRules, request: &Request) -> Vec<Mismatch> {
+  let mut mismatches = Vec::new();
+  check_rules("params", &rules.params, &mut mismatches, |name| {
+    request.params.get(name).map(|v| Value::String(v.clone()))
+  });
+  check_rules("queries", &rules.queries, &mut mismatches, |name| {
+    request.queries.get(name).map(|v| Value::String(v.clone()))
+  });
+  check_rules("headers", &rules.headers, &mut mismatches, |name| {
+    request.header(name).map(|v| Value::String(v.to_string()))
+  });
+  if let Some(body) = &rules.body {
+    check_body(body, request, &mut mismatches);
+  }
+  mismatches
+}
+
+fn check_rules<F>(kind: &'static str, rules: &[Rule], mismatches: &mut Vec<Mismatch>, actual_of: F)
+where
+  F: Fn(&str) -> Option<Value>,
+{
+  for rule in rules {
+    let actual = actual_of(&rule.name);
+    if !rule.operator.test(&rule.value, actual.as_ref()) {
+      mismatches.push(Mismatch {
+        rule: kind,
+        operator: Some(rule.operator),
+        name: Some(rule.name.clone()),
+        expected: rule.value.clone(),
+        actual: actual.unwrap_or(Value::Null),
+      });
+    }
+  }
+}
+
+fn check_body(rules: &BodyRules, request: &Request, mismatches: &mut Vec<Mismatch>) {
+  let body = parse_body(request);
+
+  match rules {
+    // The object shorthand matches the body partially, so only the listed
+    // fields have to be present.
+    BodyRules::Partial(expected) => match &body {
+      Value::Object(actual) => {
+        for (key, expected) in expected {
+          let matched = match (expected, actual.get(key)) {
+            (Value::Object(expected), Some(Value::Object(actual))) => is_subset(expected, actual),
+            (expected, Some(actual)) => equals(expected, actual),
+            (_, None) => false,
+          };
+          if !matched {
+            mismatches.push(Mismatch {
+              rule: "body",
+              operator: Some(Operator::Is),
+              name: Some(key.clone()),
+              expected: expected.clone(),
+              actual: actual.get(key).cloned().unwrap_or(Value::Null),
+            });
+          }
+        }
+      }
+      _ => mismatches.push(Mismatch::new("body", Value::Object(expected.clone()), body)),
+    },
+    BodyRules::Rules(rules) => {
+      check_rules("body", rules, mismatches, |name| body_field(&body, name))
+    }
+  }
+}
+
+/// Returns the field of a body that a rule names, either a top-level field or
+/// a JSON pointer.
+fn body_field(body: &Value, name: &str) -> Option<Value> {
+  if name.starts_with('/') || name.is_empty() {
+    body.pointer(name).cloned()
+  } else {
+    body.get(name).cloned()
+  }
+}
+
+/// Parses a request body as JSON, falling back to an URL-encoded form and
+/// finally to the raw text.
+pub fn parse_body(request: &Request) -> Value {
+  if let Ok(value) = serde_json::from_slice(&request.body) {
+    return value;
+  }
+  let text = String::from_utf8_lossy(&request.body);
+
+  let is_form = request
+    .header("Content-Type")
+    .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+  if is_form {
+    let form = text
+      .split('&')
+      .filter(|pair| !pair.is_empty())
+      .map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (decode(key), Value::String(decode(value)))
+      })
+      .collect();
+    return Value::Object(form);
+  }
+
+  Value::String(text.into_owned())
+}
+
+/// Compares two values, treating a string and a scalar with the same textual
+/// representation as equal, e.g. `"1"` and `1`.
+fn equals(expected: &Value, actual: &Value) -> bool {
+  match (expected, actual) {
+    (Value::String(text), Value::Number(_) | Value::Bool(_))
+    | (Value::Number(_) | Value::Bool(_), Value::String(text)) => {
+      let scalar = if expected.is_string() {
+        actual
+      } else {
+        expected
+      };
+      let scalar = scalar.to_string();
+      *text == scalar
+    }
+    _ => expected == actual,
+  }
+}
+
+fn contains(expected: &Value, actual: &Value) -> bool {
+  match actual {
+    Value::String(actual) => match expected {
+      Value::String(expected) => actual.contains(expected.as_str()),
+      expected => actual.contains(&expected.to_string()),
+    },
+    Value::Array(items) => items.iter().any(|item| equals(expected, item)),
+    Value::Object(actual) => match expected {
+      Value::String(key) => actual.contains_key(key),
+      Value::Object(expected) => is_subset(expected, actual),
+      _ => false,
+    },
+    _ => false,
+  }
+}
+
+fn is_subset(expected: &Map<String, Value>, actual: &Map<String, Value>) -> bool {
+  expected
+    .iter()
+    .all(|(key, expected)| match (expected, actual.get(key)) {
+      (Value::Object(expected), Some(Value::Object(actual))) => is_subset(expected, actual),
+      (expected, Some(actual)) => equals(expected, actual),
+      (_, None) => false,
+    })
+}
+
+/// Decodes a percent-encoded form component.
+fn decode(component: &str) -> String {
+  crate::web_server::decode_uri_component(&component.replace('+', " "))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ProjectConfig;
+  use serde_json::json;
+
+  fn rules_of(request: Value) -> RequestRules {
+    let config = json!({ "endpoints": [{ "path": "hello", "when": [{
+      "method": "POST",
+      "request": request,
+    }]}]});
+    let mut config = ProjectConfig::from_value(&config).unwrap();
+    config.endpoints.remove(0).when.remove(0).request
+  }
+
+  fn request(body: &str, headers: &[(&str, &str)], queries: &[(&str, &str)]) -> Request {
+    Request {
+      method: String::from("POST"),
+      path: String::from("/hello"),
+      query: String::new(),
+      version: String::from("HTTP/1.1"),
+      headers: headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect(),
+      body: body.as_bytes().to_vec(),
+      queries: queries
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect(),
+      params: HashMap::new(),
+      matches: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn query_operators() {
+    let rules = rules_of(json!({ "queries": [
+      { "operator": "is!", "name": "name", "value": "foo" },
+      { "operator": "contains", "name": "tags", "value": "b" },
+    ]}));
+
+    assert!(matches(
+      &rules,
+      &request("", &[], &[("name", "bar"), ("tags", "a,b")])
+    ));
+    assert!(!matches(
+      &rules,
+      &request("", &[], &[("name", "foo"), ("tags", "a,b")])
+    ));
+    assert!(!matches(&rules, &request("", &[], &[("name", "bar")])));
+  }
+
+  #[test]
+  fn matches_agrees_with_the_mismatches() {
+    let rules = [
+      rules_of(json!({ "queries": { "a": "1" }, "headers": { "x": "y" } })),
+      rules_of(json!({ "body": { "user": { "id": 1 } } })),
+      rules_of(json!({ "body": [{ "operator": "is!", "name": "/user/id", "value": 2 }] })),
+    ];
+    let requests = [
+      request(r#"{"user":{"id":1}}"#, &[("X", "y")], &[("a", "1")]),
+      request(r#"{"user":{"id":2}}"#, &[], &[("a", "1")]),
+      request("user=1", &[("X", "z")], &[]),
+    ];
+
+    for rules in &rules {
+      for request in &requests {
+        assert_eq!(
+          matches(rules, request),
+          mismatches_of(rules, request).is_empty()
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn header_names_are_case_insensitive() {
+    let rules = rules_of(json!({ "headers": { "content-type": "xxx" } }));
+
+    assert!(matches(
+      &rules,
+      &request("", &[("Content-Type", "xxx")], &[])
+    ));
+    assert!(!matches(
+      &rules,
+      &request("", &[("Content-Type", "yyy")], &[])
+    ));
+  }
+
+  #[test]
+  fn body_object_matches_partially() {
+    let rules = rules_of(json!({ "body": { "name": "foo", "user": { "id": 1 } } }));
+
+    assert!(matches(
+      &rules,
+      &request(r#"{"name":"foo","user":{"id":1,"role":"admin"}}"#, &[], &[])
+    ));
+    assert!(!matches(&rules, &request(r#"{"name":"foo"}"#, &[], &[])));
+    assert!(!matches(&rules, &request("name=foo", &[], &[])));
+  }
+
+  #[test]
+  fn body_rules_use_json_pointers() {
+    let rules = rules_of(json!({ "body": [
+      { "operator": "contains", "name": "/items", "value": "apple" },
+      { "operator": "contains!", "name": "note", "value": "urgent" },
+    ]}));
+
+    assert!(matches(
+      &rules,
+      &request(r#"{"items":["apple"],"note":"later"}"#, &[], &[])
+    ));
+    assert!(!matches(
+      &rules,
+      &request(r#"{"items":["pear"]}"#, &[], &[])
+    ));
+  }
+
+  #[test]
+  fn body_falls_back_to_form() {
+    let rules = rules_of(json!({ "body": { "name": "foo bar" } }));
+    let headers = [("Content-Type", "application/x-www-form-urlencoded")];
+
+    assert!(matches(&rules, &request("name=foo+bar&x=1", &headers, &[])));
+  }
+
+  #[test]
+  fn selects_the_most_specific_condition() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [
+      { "path": "users/**", "when": [{ "method": "POST" }] },
+      { "path": "users/:id", "when": [
+        { "method": "POST" },
+        { "method": "POST", "request": { "body": { "name": "foo" } } },
+        { "method": "POST", "request": { "headers": { "x": "1" } } },
+      ]},
+      { "path": "users/:id", "when": [{ "method": "POST", "priority": -1, "request": {
+        "headers": { "x": "1" }, "queries": { "y": "2" }
+      } }] },
+      { "path": "users/me", "when": [{ "method": "GET" }] },
+    ]}))
+    .unwrap();
+    let selected = |request: &mut Request| {
+      let selection = select(&config, "users/me", request, &States::new()).unwrap();
+      (selection.endpoint, selection.condition)
+    };
+
+    // The literal path loses to parameters because it does not accept POST.
+    assert_eq!(selected(&mut request("", &[], &[])), (1, 0));
+    // Ties between equally specific conditions go to the first one declared.
+    assert_eq!(
+      selected(&mut request(
+        r#"{"name":"foo"}"#,
+        &[("x", "1")],
+        &[("y", "2")]
+      )),
+      (1, 1)
+    );
+
+    let mut request = request("", &[], &[]);
+    request.method = String::from("GET");
+    assert_eq!(selected(&mut request), (3, 0));
+  }
+
+  #[test]
+  fn exact_paths_win_over_rest_wildcards() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [
+      { "path": "users/**", "when": [{ "method": "GET" }] },
+      { "path": "users", "when": [{ "method": "GET" }] },
+    ]}))
+    .unwrap();
+    let mut request = request("", &[], &[]);
+    request.method = String::from("GET");
+
+    let selection = select(&config, "users", &mut request, &States::new()).unwrap();
+    assert_eq!(selection.endpoint, 1);
+    let selection = select(&config, "users/a/b", &mut request, &States::new()).unwrap();
+    assert_eq!(selection.endpoint, 0);
+  }
+
+  #[test]
+  fn head_requests_fall_back_to_get_conditions() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [
+      { "path": "users", "when": [{ "method": "GET" }] },
+      { "path": "files", "when": [{ "method": "GET" }, { "method": "HEAD" }] },
+      { "path": "orders", "when": [{ "method": "POST" }] },
+    ]}))
+    .unwrap();
+    let mut request = request("", &[], &[]);
+    request.method = String::from("HEAD");
+
+    let selection = select(&config, "users", &mut request, &States::new()).unwrap();
+    assert_eq!(selection.condition, 0);
+    let selection = select(&config, "files", &mut request, &States::new()).unwrap();
+    assert_eq!(selection.condition, 1);
+    assert!(select(&config, "orders", &mut request, &States::new()).is_none());
+  }
+
+  #[test]
+  fn selects_conditions_by_scenario_state() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [{ "path": "order", "when": [
+      { "method": "POST", "scenario": "pay", "requiredState": "Started", "newState": "paid" },
+      { "method": "POST", "scenario": "pay", "requiredState": "paid" },
+    ]}]}))
+    .unwrap();
+    let mut states = States::new();
+    let mut request = request("", &[], &[]);
+
+    let selection = select(&config, "order", &mut request, &states).unwrap();
+    assert_eq!(selection.condition, 0);
+
+    states.insert(String::from("pay"), String::from("paid"));
+    let selection = select(&config, "order", &mut request, &states).unwrap();
+    assert_eq!(selection.condition, 1);
+
+    states.insert(String::from("pay"), String::from("refunded"));
+    assert!(select(&config, "order", &mut request, &states).is_none());
+    let near_misses = diagnose(&config, "order", &mut request, &states, 1);
+    assert_eq!(near_misses[0].mismatches[0].rule, "state");
+    assert_eq!(near_misses[0].mismatches[0].actual, json!("refunded"));
+  }
+
+  #[test]
+  fn diagnoses_the_closest_conditions() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [
+      { "path": "orders", "when": [{ "method": "POST" }] },
+      { "path": "users/:id", "when": [
+        { "method": "GET" },
+        { "method": "POST", "request": {
+          "headers": { "x": "1" },
+          "body": [{ "operator": "contains", "name": "/tags", "value": "a" }],
+        } },
+      ]},
+    ]}))
+    .unwrap();
+    let mut request = request(r#"{"tags":["b"]}"#, &[("x", "2")], &[]);
+
+    let near_misses = diagnose(&config, "users/7", &mut request, &States::new(), 2);
+
+    assert_eq!(near_misses.len(), 2);
+    assert_eq!((near_misses[0].endpoint, near_misses[0].condition), (1, 0));
+    assert_eq!(near_misses[0].mismatches[0].rule, "method");
+    let mismatches = &near_misses[1].mismatches;
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].rule, "headers");
+    assert_eq!(mismatches[0].actual, json!("2"));
+    assert_eq!(mismatches[1].operator, Some(Operator::Contains));
+    assert_eq!(mismatches[1].actual, json!(["b"]));
+  }
+
+  #[test]
+  fn verifies_recorded_requests() {
+    let verification = Verification::parse(
+      br#"{ "method": "POST", "path": "payments/:id", "request": {
+        "params": { "id": "7" },
+        "body": { "amount": 10 }
+      } }"#,
+    )
+    .unwrap();
+    let entry = |method: &str, path: &str, body: &str| Entry {
+      id: 1,
+      project: String::from("shop"),
+      method: method.to_string(),
+      path: path.to_string(),
+      queries: HashMap::new(),
+      headers: HashMap::new(),
+      body: body.as_bytes().to_vec(),
+      matched: None,
+      status: 200,
+      received_at: std::time::SystemTime::now(),
+      duration: std::time::Duration::ZERO,
+    };
+
+    assert!(verifies(
+      &verification,
+      &entry("POST", "payments/7", r#"{"amount":10}"#)
+    ));
+    assert!(!verifies(
+      &verification,
+      &entry("GET", "payments/7", r#"{"amount":10}"#)
+    ));
+    assert!(!verifies(
+      &verification,
+      &entry("POST", "payments/8", r#"{"amount":10}"#)
+    ));
+    assert!(!verifies(
+      &verification,
+      &entry("POST", "payments/7", r#"{"amount":1}"#)
+    ));
+  }
+}
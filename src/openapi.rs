@@ -0,0 +1,890 @@
+use crate::{
+  base64,
+  config::{
+    self, BodyRules, Condition, MockResponse, Operator, Problem, ProjectConfig, RequestRules,
+    ResponseBody, Rule,
+  },
+  web_server::{
+    self,
+    types::{PathPattern, Segment},
+  },
+};
+use serde_json::{json, Map, Value};
+
+/// The operations of a path item, by their key.
+const METHODS: [&str; 8] = [
+  "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// How many references in a row may be followed before giving up on a loop.
+const MAX_REFERENCES: usize = 16;
+
+/// How many schemas may be visited to synthesize a body, which bounds its size
+/// when the references fan out.
+const MAX_SCHEMAS: usize = 1000;
+
+static NULL: Value = Value::Null;
+
+/// Generates a project's config from an OpenAPI 3 JSON document, with an
+/// endpoint per path and a condition per operation, which answers with the
+/// documented responses. The problems point into the OpenAPI document.
+pub fn import(document: &[u8]) -> Result<Value, Vec<Problem>> {
+  let spec = config::parse_json(document)?;
+  let mut importer = Importer {
+    spec: &spec,
+    problems: Vec::new(),
+    budget: 0,
+  };
+  let config = importer.project();
+
+  if importer.problems.is_empty() {
+    Ok(config)
+  } else {
+    Err(importer.problems)
+  }
+}
+
+struct Importer<'a> {
+  spec: &'a Value,
+  problems: Vec<Problem>,
+  /// How many more schemas the body being synthesized may visit.
+  budget: usize,
+}
+
+impl<'a> Importer<'a> {
+  fn problem(&mut self, pointer: &str, message: &str) {
+    self.problems.push(Problem {
+      pointer: pointer.to_string(),
+      message: message.to_string(),
+    });
+  }
+
+  fn project(&mut self) -> Value {
+    match self.spec["openapi"].as_str() {
+      Some(version) if version.starts_with("3.") => {}
+      _ => {
+        self.problem("/openapi", "Must be an OpenAPI 3 version, e.g. `3.0.3`.");
+        return Value::Null;
+      }
+    }
+    let paths = match self.spec["paths"].as_object() {
+      Some(paths) => paths,
+      None => {
+        self.problem("/paths", "Must be an object.");
+        return Value::Null;
+      }
+    };
+
+    let mut endpoints = Vec::new();
+    for (path, item) in paths {
+      let pointer = format!("/paths/{}", config::escape_pointer(path));
+      let item = self.resolve(item, &pointer);
+      let when: Vec<Value> = METHODS
+        .iter()
+        .filter(|method| !item[**method].is_null())
+        .flat_map(|method| {
+          self.conditions(method, &item[*method], &format!("{}/{}", pointer, method))
+        })
+        .collect();
+
+      if !when.is_empty() {
+        endpoints.push(json!({ "path": endpoint_path(path), "when": when }));
+      }
+    }
+
+    let mut config = json!({ "endpoints": endpoints });
+    if let Some(title) = self.spec["info"]["title"].as_str() {
+      config["description"] = json!(title);
+    }
+    config
+  }
+
+  /// Returns the conditions of an operation. The successful response, or else
+  /// the first documented one, answers by default, whereas each other one is
+  /// only answered when asked for with a `Prefer: code=<status>` header.
+  fn conditions(&mut self, method: &str, operation: &'a Value, pointer: &str) -> Vec<Value> {
+    let documented = operation["responses"].as_object();
+    let count = documented.map_or(0, |d| d.len());
+
+    let mut responses = Vec::new();
+    for (key, response) in documented.into_iter().flatten() {
+      let pointer = format!("{}/responses/{}", pointer, config::escape_pointer(key));
+      match status(key, count) {
+        Some(status) => {
+          let response = self.resolve(response, &pointer);
+          responses.push((status, self.response(status, response, &pointer)));
+        }
+        None => self.problem(
+          &pointer,
+          "Must be an HTTP status, a range such as `4XX` or `default`.",
+        ),
+      }
+    }
+    responses.sort_by_key(|(status, _)| (!(200..300).contains(status), *status));
+
+    let method = method.to_uppercase();
+    let mut responses = responses.into_iter();
+    let default = match responses.next() {
+      Some((_, response)) => response,
+      None => json!({ "status": 200 }),
+    };
+    let mut conditions = vec![json!({ "method": method, "response": default })];
+    for (status, response) in responses {
+      let prefer =
+        json!({ "operator": "contains", "name": "Prefer", "value": format!("code={}", status) });
+      conditions.push(json!({
+        "method": method,
+        "request": { "headers": [prefer] },
+        "response": response,
+      }));
+    }
+    conditions
+  }
+
+  /// Returns the mock response of a documented one, preferring its JSON
+  /// content.
+  fn response(&mut self, status: u16, response: &'a Value, pointer: &str) -> Value {
+    let mut mock = json!({ "status": status });
+    let content = match response["content"].as_object() {
+      Some(content) => content,
+      None => return mock,
+    };
+    let media = content
+      .iter()
+      .find(|(media_type, _)| is_json(media_type))
+      .or_else(|| content.iter().next());
+
+    if let Some((media_type, media)) = media {
+      let pointer = format!("{}/content/{}", pointer, config::escape_pointer(media_type));
+      let body = self.example(media, &pointer);
+      if !media_type.contains('*') {
+        mock["headers"] = json!({ "Content-Type": media_type });
+      }
+      match body {
+        Some(body) if is_json(media_type) => mock["body"] = body,
+        Some(Value::String(text)) => mock["bodyBase64"] = json!(base64::encode(text.as_bytes())),
+        _ => {}
+      }
+    }
+    mock
+  }
+
+  /// Returns the example of a media type, or one synthesized from its schema.
+  fn example(&mut self, media: &'a Value, pointer: &str) -> Option<Value> {
+    if let Some(example) = media.get("example") {
+      return Some(example.clone());
+    }
+    if let Some((name, example)) = media["examples"].as_object().and_then(|e| e.iter().next()) {
+      let pointer = format!("{}/examples/{}", pointer, config::escape_pointer(name));
+      let example = self.resolve(example, &pointer);
+      if let Some(value) = example.get("value") {
+        return Some(value.clone());
+      }
+    }
+
+    let schema = media.get("schema")?;
+    self.budget = MAX_SCHEMAS;
+    self.synthesize(schema, &format!("{}/schema", pointer), &mut Vec::new())
+  }
+
+  /// Builds a value that satisfies a schema, from its examples, defaults and
+  /// types. `references` holds the ones being followed, so that a recursive
+  /// schema ends where it would repeat itself, and the parts of the schema past
+  /// the budget are left out.
+  fn synthesize(
+    &mut self,
+    schema: &'a Value,
+    pointer: &str,
+    references: &mut Vec<&'a str>,
+  ) -> Option<Value> {
+    if self.budget == 0 {
+      return None;
+    }
+    self.budget -= 1;
+
+    if let Some(reference) = schema["$ref"].as_str() {
+      if references.contains(&reference) {
+        return None;
+      }
+      let target = self.reference(reference, pointer)?;
+      references.push(reference);
+      let value = self.synthesize(target, &reference[1..], references);
+      references.pop();
+      return value;
+    }
+
+    let example = schema
+      .get("example")
+      .or_else(|| schema["examples"].as_array().and_then(|e| e.first()))
+      .or_else(|| schema.get("default"))
+      .or_else(|| schema.get("const"))
+      .or_else(|| schema["enum"].as_array().and_then(|e| e.first()));
+    if let Some(example) = example {
+      return Some(example.clone());
+    }
+
+    if let Some(parts) = schema["allOf"].as_array() {
+      let mut fields = Map::new();
+      let mut other = None;
+      for (i, part) in parts.iter().enumerate() {
+        match self.synthesize(part, &format!("{}/allOf/{}", pointer, i), references) {
+          Some(Value::Object(part)) => fields.extend(part),
+          value => other = other.or(value),
+        }
+      }
+      return if fields.is_empty() {
+        other
+      } else {
+        Some(Value::Object(fields))
+      };
+    }
+    for key in ["oneOf", "anyOf"] {
+      if let Some(first) = schema[key].as_array().and_then(|s| s.first()) {
+        return self.synthesize(first, &format!("{}/{}/0", pointer, key), references);
+      }
+    }
+
+    let kind = match &schema["type"] {
+      Value::String(kind) => kind.as_str(),
+      // OpenAPI 3.1 lists the types, e.g. `["string", "null"]`.
+      Value::Array(kinds) => kinds
+        .iter()
+        .filter_map(|k| k.as_str())
+        .find(|k| *k != "null")
+        .unwrap_or("null"),
+      _ if !schema["properties"].is_null() => "object",
+      _ if !schema["items"].is_null() => "array",
+      _ => "",
+    };
+    match kind {
+      "object" => {
+        let mut object = Map::new();
+        for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+          let pointer = format!("{}/properties/{}", pointer, config::escape_pointer(name));
+          if let Some(value) = self.synthesize(property, &pointer, references) {
+            object.insert(name.clone(), value);
+          }
+        }
+        Some(Value::Object(object))
+      }
+      "array" => {
+        let item = self.synthesize(&schema["items"], &format!("{}/items", pointer), references);
+        Some(Value::Array(item.into_iter().collect()))
+      }
+      "string" => Some(json!(string_example(schema["format"].as_str()))),
+      "integer" => Some(json!(schema["minimum"].as_i64().unwrap_or(0))),
+      "number" => Some(json!(schema["minimum"].as_f64().unwrap_or(0.0))),
+      "boolean" => Some(json!(true)),
+      "null" => Some(Value::Null),
+      _ => None,
+    }
+  }
+
+  /// Follows the references of an object, such as a response declared under
+  /// `components`, to the object itself.
+  fn resolve(&mut self, value: &'a Value, pointer: &str) -> &'a Value {
+    let mut value = value;
+    let mut pointer = pointer.to_string();
+
+    for _ in 0..MAX_REFERENCES {
+      let reference = match value["$ref"].as_str() {
+        Some(reference) => reference,
+        None => return value,
+      };
+      value = match self.reference(reference, &pointer) {
+        Some(target) => target,
+        None => return &NULL,
+      };
+      pointer = reference[1..].to_string();
+    }
+
+    self.problem(&pointer, "Has too many nested references.");
+    &NULL
+  }
+
+  fn reference(&mut self, reference: &str, pointer: &str) -> Option<&'a Value> {
+    let target = reference
+      .strip_prefix('#')
+      .and_then(|target| self.spec.pointer(target));
+    if target.is_none() {
+      self.problem(
+        &format!("{}/$ref", pointer),
+        &format!(
+          "Unknown reference `{}`. Only references within the document are supported.",
+          reference
+        ),
+      );
+    }
+    target
+  }
+}
+
+/// Converts an OpenAPI path into an endpoint's one, where `{id}` becomes the
+/// parameter `:id`. Segments that mix literals with parameters, such as
+/// `{name}.json`, become wildcards.
+fn endpoint_path(path: &str) -> String {
+  path
+    .trim_matches('/')
+    .split('/')
+    .map(|segment| {
+      let name = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}'));
+      match name {
+        Some(name) if !name.contains(['{', '}']) => format!(":{}", name),
+        _ if segment.contains('{') => String::from("*"),
+        _ => segment.to_string(),
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// Returns the status of a response's key. A range stands for its first
+/// status, and `default` for a success when it is the only response, or for a
+/// server error otherwise.
+fn status(key: &str, count: usize) -> Option<u16> {
+  match key.to_uppercase().as_str() {
+    "DEFAULT" if count == 1 => Some(200),
+    "DEFAULT" => Some(500),
+    range if range.len() == 3 && range.ends_with("XX") => range[..1]
+      .parse::<u16>()
+      .ok()
+      .filter(|class| (1..=5).contains(class))
+      .map(|class| class * 100),
+    status => status.parse().ok().filter(|s| (100..=599).contains(s)),
+  }
+}
+
+fn is_json(media_type: &str) -> bool {
+  let essence = media_type.split(';').next().unwrap_or("").trim();
+  essence == "application/json" || essence.ends_with("+json")
+}
+
+fn string_example(format: Option<&str>) -> &'static str {
+  match format {
+    Some("date-time") => "1970-01-01T00:00:00Z",
+    Some("date") => "1970-01-01",
+    Some("time") => "00:00:00",
+    Some("email") => "user@example.com",
+    Some("uuid") => "00000000-0000-0000-0000-000000000000",
+    Some("uri" | "url") => "https://example.com",
+    Some("hostname") => "example.com",
+    Some("ipv4") => "127.0.0.1",
+    Some("ipv6") => "::1",
+    Some("byte") => "c3RyaW5n",
+    _ => "string",
+  }
+}
+
+/// Renders a project's config as an OpenAPI 3 document, whose server is the
+/// project's mock route. The conditions of an endpoint's method make up one
+/// operation, whose parameters are the ones their rules check and whose
+/// examples are their responses' bodies.
+pub fn export(name: &str, config: &ProjectConfig) -> Value {
+  let mut paths = Map::new();
+
+  for endpoint in &config.endpoints {
+    let (path, path_parameters) = openapi_path(&endpoint.pattern);
+    let item = paths.entry(path).or_insert_with(|| json!({}));
+
+    let mut methods: Vec<&str> = Vec::new();
+    for condition in &endpoint.when {
+      if !methods.contains(&condition.method.as_str()) {
+        methods.push(&condition.method);
+      }
+    }
+    for method in methods {
+      let key = method.to_lowercase();
+      // Other methods can not be described, and an operation that was already
+      // described by a previous endpoint is the one that answers.
+      if !METHODS.contains(&key.as_str()) || !item[&key].is_null() {
+        continue;
+      }
+      let conditions: Vec<&Condition> = endpoint
+        .when
+        .iter()
+        .filter(|c| c.method == method)
+        .collect();
+      item[&key] = operation(&path_parameters, &conditions);
+    }
+  }
+
+  let title = if config.description.is_empty() {
+    name
+  } else {
+    &config.description
+  };
+  json!({
+    "openapi": "3.0.3",
+    "info": { "title": title, "version": "1.0.0" },
+    "servers": [{ "url": format!("/projects/{}", name) }],
+    "paths": paths,
+  })
+}
+
+/// Converts an endpoint's path pattern into an OpenAPI path, along with the
+/// names and schemas of its parameters. The wildcards are named after their
+/// index.
+fn openapi_path(pattern: &PathPattern) -> (String, Vec<(String, Value)>) {
+  let mut parameters = Vec::new();
+  let segments: Vec<String> = pattern
+    .segments
+    .iter()
+    .map(|segment| {
+      let (name, schema) = match segment {
+        Segment::Literal(literal) => return literal.clone(),
+        Segment::Param(name, None) | Segment::Wildcard(name) => (name, json!({ "type": "string" })),
+        Segment::Param(name, Some(regex)) => {
+          (name, json!({ "type": "string", "pattern": regex.as_str() }))
+        }
+        // A path parameter can not hold several segments, which is only
+        // stated.
+        Segment::Rest(name) => (
+          name,
+          json!({ "type": "string", "description": "Any number of segments." }),
+        ),
+      };
+      parameters.push((name.clone(), schema));
+      format!("{{{}}}", name)
+    })
+    .collect();
+
+  (format!("/{}", segments.join("/")), parameters)
+}
+
+/// Describes the conditions of an endpoint's method as an operation.
+fn operation(path_parameters: &[(String, Value)], conditions: &[&Condition]) -> Value {
+  let mut parameters: Vec<Value> = path_parameters
+    .iter()
+    .map(|(name, schema)| {
+      let mut parameter = json!({ "name": name, "in": "path", "required": true, "schema": schema });
+      let rules = conditions.iter().flat_map(|c| &c.request.params);
+      if let Some(example) = example_of(rules, name) {
+        parameter["example"] = example;
+      }
+      parameter
+    })
+    .collect();
+  parameters.extend(rule_parameters(conditions, "query", |r| &r.queries));
+  parameters.extend(rule_parameters(conditions, "header", |r| &r.headers));
+
+  let mut operation = json!({ "responses": responses(conditions) });
+  if !parameters.is_empty() {
+    operation["parameters"] = json!(parameters);
+  }
+  let body = conditions
+    .iter()
+    .find_map(|c| c.request.body.as_ref().and_then(body_example));
+  if let Some(body) = body {
+    operation["requestBody"] = json!({ "content": { "application/json": {
+      "schema": schema_of(&body),
+      "example": body,
+    }}});
+  }
+  operation
+}
+
+/// Describes the queries or headers checked by the conditions as parameters,
+/// which are required when every condition expects them.
+fn rule_parameters(
+  conditions: &[&Condition],
+  location: &str,
+  rules_of: fn(&RequestRules) -> &Vec<Rule>,
+) -> Vec<Value> {
+  let mut names: Vec<&str> = Vec::new();
+  for rule in conditions.iter().flat_map(|c| rules_of(&c.request)) {
+    if !names.iter().any(|n| n.eq_ignore_ascii_case(&rule.name)) {
+      names.push(&rule.name);
+    }
+  }
+
+  names
+    .into_iter()
+    .map(|name| {
+      let expects = |rule: &Rule| {
+        rule.name.eq_ignore_ascii_case(name)
+          && matches!(rule.operator, Operator::Is | Operator::Contains)
+      };
+      let required = conditions
+        .iter()
+        .all(|c| rules_of(&c.request).iter().any(expects));
+
+      let mut parameter = json!({
+        "name": name,
+        "in": location,
+        "required": required,
+        "schema": { "type": "string" },
+      });
+      let rules = conditions.iter().flat_map(|c| rules_of(&c.request));
+      if let Some(example) = example_of(rules, name) {
+        parameter["example"] = example;
+      }
+      parameter
+    })
+    .collect()
+}
+
+/// Returns the value of the first `is` rule on a name, as a string.
+fn example_of<'a>(rules: impl Iterator<Item = &'a Rule>, name: &str) -> Option<Value> {
+  let rule = rules
+    .filter(|r| r.operator == Operator::Is)
+    .find(|r| r.name.eq_ignore_ascii_case(name))?;
+  match &rule.value {
+    Value::String(value) => Some(json!(value)),
+    value => Some(json!(value.to_string())),
+  }
+}
+
+/// Returns a request body that the body rules expect: the fields of the object
+/// shorthand, or the values of the `is` rules.
+fn body_example(rules: &BodyRules) -> Option<Value> {
+  let rules = match rules {
+    BodyRules::Partial(fields) => return Some(Value::Object(fields.clone())),
+    BodyRules::Rules(rules) => rules,
+  };
+
+  let mut body = json!({});
+  for rule in rules.iter().filter(|r| r.operator == Operator::Is) {
+    let keys: Vec<String> = match rule.name.strip_prefix('/') {
+      Some(pointer) => pointer
+        .split('/')
+        .map(|key| key.replace("~1", "/").replace("~0", "~"))
+        .collect(),
+      None => vec![rule.name.clone()],
+    };
+    let mut field = &mut body;
+    for key in keys {
+      if !field.is_object() {
+        *field = json!({});
+      }
+      field = field
+        .as_object_mut()
+        .unwrap()
+        .entry(key)
+        .or_insert(Value::Null);
+    }
+    *field = rule.value.clone();
+  }
+
+  body
+    .as_object()
+    .is_some_and(|b| !b.is_empty())
+    .then_some(body)
+}
+
+/// Describes the responses of the conditions by status, with their bodies as
+/// examples.
+fn responses(conditions: &[&Condition]) -> Value {
+  let mut statuses: Vec<(u16, Vec<&MockResponse>)> = Vec::new();
+  for response in conditions.iter().flat_map(|c| &c.responses) {
+    match statuses
+      .iter_mut()
+      .find(|(status, _)| *status == response.status)
+    {
+      Some((_, responses)) => responses.push(response),
+      None => statuses.push((response.status, vec![response])),
+    }
+  }
+
+  let mut described = Map::new();
+  for (status, responses) in statuses {
+    let description = match web_server::reason_phrase(status) {
+      "" => format!("Status {}.", status),
+      reason => reason.to_string(),
+    };
+    let mut response = json!({ "description": description });
+
+    let mut headers = Map::new();
+    let mut contents: Vec<(String, Value, Vec<Value>)> = Vec::new();
+    for mock in responses {
+      for (name, value) in &mock.headers {
+        if !name.eq_ignore_ascii_case("Content-Type") && !headers.contains_key(name) {
+          headers.insert(
+            name.clone(),
+            json!({ "schema": { "type": "string" }, "example": value }),
+          );
+        }
+      }
+
+      let (media_type, schema, example) = match content(mock) {
+        Some(content) => content,
+        None => continue,
+      };
+      match contents.iter_mut().find(|(t, _, _)| *t == media_type) {
+        Some((_, _, examples)) => examples.extend(example),
+        None => contents.push((media_type, schema, example.into_iter().collect())),
+      }
+    }
+
+    if !headers.is_empty() {
+      response["headers"] = Value::Object(headers);
+    }
+    if !contents.is_empty() {
+      let mut content = Map::new();
+      for (media_type, schema, mut examples) in contents {
+        examples.dedup();
+        let mut media = json!({ "schema": schema });
+        match examples.len() {
+          0 => {}
+          1 => media["example"] = examples.remove(0),
+          _ => {
+            let examples: Map<String, Value> = examples
+              .into_iter()
+              .enumerate()
+              .map(|(i, value)| (format!("example{}", i + 1), json!({ "value": value })))
+              .collect();
+            media["examples"] = Value::Object(examples);
+          }
+        }
+        content.insert(media_type, media);
+      }
+      response["content"] = Value::Object(content);
+    }
+
+    described.insert(status.to_string(), response);
+  }
+  Value::Object(described)
+}
+
+/// Returns the media type, schema and example of a response's body, unless it
+/// is empty.
+fn content(response: &MockResponse) -> Option<(String, Value, Option<Value>)> {
+  let declared = response
+    .headers
+    .iter()
+    .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+    .map(|(_, value)| value.clone());
+  let binary = json!({ "type": "string", "format": "binary" });
+
+  let (default_type, schema, example) = match &response.body {
+    ResponseBody::Json(Value::Null) => return None,
+    ResponseBody::Json(body) => ("application/json", schema_of(body), Some(body.clone())),
+    ResponseBody::Bytes(bytes) => match String::from_utf8(bytes.clone()) {
+      Ok(text) => ("text/plain", json!({ "type": "string" }), Some(json!(text))),
+      Err(_) => ("application/octet-stream", binary, None),
+    },
+    ResponseBody::File(_) => ("application/octet-stream", binary, None),
+  };
+  let media_type = declared.unwrap_or_else(|| default_type.to_string());
+  Some((media_type, schema, example))
+}
+
+/// Infers the schema of a JSON value.
+fn schema_of(value: &Value) -> Value {
+  match value {
+    Value::Null => json!({ "nullable": true }),
+    Value::Bool(_) => json!({ "type": "boolean" }),
+    Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+    Value::Number(_) => json!({ "type": "integer" }),
+    Value::String(_) => json!({ "type": "string" }),
+    Value::Array(items) => {
+      let items = items.first().map_or(json!({}), schema_of);
+      json!({ "type": "array", "items": items })
+    }
+    Value::Object(fields) => {
+      let properties: Map<String, Value> = fields
+        .iter()
+        .map(|(name, value)| (name.clone(), schema_of(value)))
+        .collect();
+      json!({ "type": "object", "properties": properties })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ProjectConfig;
+
+  fn import_value(spec: Value) -> Result<Value, Vec<Problem>> {
+    import(spec.to_string().as_bytes())
+  }
+
+  #[test]
+  fn generates_endpoints_per_path_and_conditions_per_operation() {
+    let config = import_value(json!({
+      "openapi": "3.0.3",
+      "info": { "title": "Users", "version": "1" },
+      "paths": {
+        "/users/{id}": {
+          "get": { "responses": {
+            "404": { "description": "Missing" },
+            "200": { "content": { "application/json": { "example": { "id": 1 } } } },
+          }},
+          "delete": { "responses": { "204": { "description": "Deleted" } } },
+        },
+        "/files/{name}.txt": {
+          "get": { "responses": { "default": {
+            "content": { "text/plain": { "schema": { "type": "string", "example": "hi" } } },
+          }}},
+        },
+      },
+    }))
+    .unwrap();
+
+    assert_eq!(config["description"], "Users");
+    let files = &config["endpoints"][0];
+    assert_eq!(files["path"], "files/*");
+    assert_eq!(files["when"][0]["response"]["status"], 200);
+    assert_eq!(files["when"][0]["response"]["bodyBase64"], "aGk=");
+
+    let users = &config["endpoints"][1];
+    assert_eq!(users["path"], "users/:id");
+    assert_eq!(users["when"][0]["method"], "GET");
+    assert!(users["when"][0]["request"].is_null());
+    assert_eq!(users["when"][0]["response"]["status"], 200);
+    assert_eq!(users["when"][0]["response"]["body"], json!({ "id": 1 }));
+    assert_eq!(users["when"][1]["method"], "GET");
+    assert_eq!(
+      users["when"][1]["request"]["headers"],
+      json!([{ "operator": "contains", "name": "Prefer", "value": "code=404" }])
+    );
+    assert_eq!(users["when"][1]["response"]["status"], 404);
+    assert_eq!(users["when"][2]["method"], "DELETE");
+    assert_eq!(users["when"][2]["response"], json!({ "status": 204 }));
+
+    assert!(ProjectConfig::from_value(&config).is_ok());
+  }
+
+  #[test]
+  fn synthesizes_bodies_from_schemas() {
+    let config = import_value(json!({
+      "openapi": "3.1.0",
+      "paths": { "/nodes": { "get": { "responses": { "200": { "$ref": "#/components/responses/Nodes" } } } } },
+      "components": {
+        "responses": { "Nodes": { "content": { "application/json": {
+          "schema": { "type": "array", "items": { "$ref": "#/components/schemas/Node" } },
+        }}}},
+        "schemas": {
+          "Node": { "allOf": [
+            { "type": "object", "properties": { "id": { "type": "string", "format": "uuid" } } },
+            { "properties": {
+              "kind": { "enum": ["leaf", "branch"] },
+              "size": { "type": ["integer", "null"], "minimum": 1 },
+              "children": { "type": "array", "items": { "$ref": "#/components/schemas/Node" } },
+            }},
+          ]},
+        },
+      },
+    }))
+    .unwrap();
+
+    assert_eq!(
+      config["endpoints"][0]["when"][0]["response"]["body"],
+      json!([{
+        "id": "00000000-0000-0000-0000-000000000000",
+        "kind": "leaf",
+        "size": 1,
+        "children": [],
+      }])
+    );
+  }
+
+  #[test]
+  fn bounds_bodies_of_fanning_out_references() {
+    // Every level doubles the size of the body, up to 2^30 leaves.
+    let mut schemas = Map::new();
+    for i in 0..30 {
+      let next = json!({ "$ref": format!("#/components/schemas/S{}", i + 1) });
+      schemas.insert(
+        format!("S{}", i),
+        json!({ "properties": { "a": next, "b": next } }),
+      );
+    }
+    schemas.insert(String::from("S30"), json!({ "type": "integer" }));
+    let config = import_value(json!({
+      "openapi": "3.0.0",
+      "paths": { "/a": { "get": { "responses": { "200": { "content": { "application/json": {
+        "schema": { "$ref": "#/components/schemas/S0" },
+      }}}}}}},
+      "components": { "schemas": schemas },
+    }))
+    .unwrap();
+
+    let body = config["endpoints"][0]["when"][0]["response"]["body"].to_string();
+    assert!(body.starts_with(r#"{"a":{"a":"#));
+    assert!(body.matches('{').count() < MAX_SCHEMAS);
+  }
+
+  #[test]
+  fn reports_problems() {
+    let problems = import_value(json!({ "swagger": "2.0" })).unwrap_err();
+    assert_eq!(problems[0].pointer, "/openapi");
+
+    let problems = import_value(json!({
+      "openapi": "3.0.0",
+      "paths": { "/a": { "get": { "responses": {
+        "ok": {},
+        "200": { "$ref": "other.json#/Ok" },
+      }}}},
+    }))
+    .unwrap_err();
+    let pointers: Vec<&str> = problems.iter().map(|p| p.pointer.as_str()).collect();
+    assert_eq!(
+      pointers,
+      [
+        "/paths/~1a/get/responses/200/$ref",
+        "/paths/~1a/get/responses/ok"
+      ]
+    );
+  }
+
+  #[test]
+  fn exports_projects() {
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [{
+      "path": "users/:id(\\d+)",
+      "when": [
+        {
+          "method": "GET",
+          "request": { "queries": { "full": "1" }, "headers": { "token": "a" } },
+          "response": { "status": 200, "headers": { "X-Id": "1" }, "body": { "id": 1, "tags": ["a"] } },
+        },
+        {
+          "method": "GET",
+          "request": { "headers": { "Token": "b" } },
+          "responses": [{ "status": 200, "body": { "id": 2 } }, { "status": 404 }],
+        },
+        {
+          "method": "PUT",
+          "request": { "body": [{ "operator": "is", "name": "/user/name", "value": "Ann" }] },
+          "response": { "status": 204 },
+        },
+      ],
+    }]}))
+    .unwrap();
+
+    let spec = export("demo", &config);
+    assert_eq!(spec["info"]["title"], "demo");
+    assert_eq!(spec["servers"][0]["url"], "/projects/demo");
+
+    let get = &spec["paths"]["/users/{id}"]["get"];
+    assert_eq!(
+      get["parameters"],
+      json!([
+        { "name": "id", "in": "path", "required": true, "schema": { "type": "string", "pattern": "^(?:\\d+)$" } },
+        { "name": "full", "in": "query", "required": false, "schema": { "type": "string" }, "example": "1" },
+        { "name": "token", "in": "header", "required": true, "schema": { "type": "string" }, "example": "a" },
+      ])
+    );
+    let ok = &get["responses"]["200"];
+    assert_eq!(ok["description"], "OK");
+    assert_eq!(ok["headers"]["X-Id"]["example"], "1");
+    assert_eq!(
+      ok["content"]["application/json"]["schema"]["properties"]["tags"],
+      json!({ "type": "array", "items": { "type": "string" } })
+    );
+    assert_eq!(
+      ok["content"]["application/json"]["examples"]["example2"]["value"],
+      json!({ "id": 2 })
+    );
+    assert_eq!(
+      get["responses"]["404"],
+      json!({ "description": "Not Found" })
+    );
+
+    let put = &spec["paths"]["/users/{id}"]["put"];
+    assert_eq!(
+      put["requestBody"]["content"]["application/json"]["example"],
+      json!({ "user": { "name": "Ann" } })
+    );
+  }
+}
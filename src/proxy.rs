@@ -0,0 +1,182 @@
+use crate::{
+  base64,
+  cache::{LoadError, ProjectCache},
+  config::{Problem, Target},
+  web_server::{
+    client,
+    types::{Limits, ParseError, Request, Response},
+  },
+};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+/// How long the target may take to accept the connection or to send data.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Forwards a request to a path of a project to the proxy's target, keeping its
+/// method, query string, headers and body.
+pub fn forward(target: &Target, path: &str, request: &Request) -> Result<Response, ParseError> {
+  let mut target_path = format!("{}/{}", target.base_path, path);
+  if !request.query.is_empty() {
+    target_path.push('?');
+    target_path.push_str(&request.query);
+  }
+
+  let upstream = Request {
+    method: request.method.clone(),
+    path: target_path,
+    query: request.query.clone(),
+    version: String::from("HTTP/1.1"),
+    headers: request.headers.clone(),
+    body: request.body.clone(),
+    queries: request.queries.clone(),
+    params: request.params.clone(),
+    matches: Vec::new(),
+  };
+  client::send(
+    &target.host,
+    target.port,
+    &upstream,
+    TIMEOUT,
+    &Limits::default(),
+  )
+}
+
+/// Adds a forwarded request and its response to a project, as a condition of
+/// the endpoint of the request's path. The condition matches the request's
+/// method, queries and JSON body, so that the same request is answered by the
+/// recording from then on.
+pub fn record(
+  cache: &ProjectCache,
+  project: &str,
+  path: &str,
+  request: &Request,
+  response: &Response,
+) -> Result<(), LoadError> {
+  // The date of the recording would be stale when it is replayed.
+  let condition = recorded_condition(request, response, &["Date"]);
+
+  cache.update(project, |document| {
+    let mut document: Value = serde_json::from_slice(document).map_err(|error| {
+      LoadError::Invalid(vec![Problem {
+        pointer: String::new(),
+        message: format!("Invalid JSON: {}.", error),
+      }])
+    })?;
+
+    let endpoints = match document["endpoints"].as_array_mut() {
+      Some(endpoints) => endpoints,
+      None => {
+        document["endpoints"] = json!([]);
+        document["endpoints"].as_array_mut().unwrap()
+      }
+    };
+    let endpoint = endpoints
+      .iter_mut()
+      .find(|e| e["path"].as_str().map(|p| p.trim_matches('/')) == Some(path));
+    match endpoint {
+      Some(endpoint) => match endpoint["when"].as_array_mut() {
+        Some(when) => when.push(condition),
+        None => endpoint["when"] = json!([condition]),
+      },
+      None => endpoints.push(json!({ "path": path, "when": [condition] })),
+    }
+
+    Ok(serde_json::to_vec_pretty(&document).unwrap())
+  })?;
+
+  Ok(())
+}
+
+/// Returns a condition that answers a request with its recorded response,
+/// without the response's headers listed in `strip`.
+pub fn recorded_condition(request: &Request, response: &Response, strip: &[&str]) -> Value {
+  let mut rules = Map::new();
+  if !request.queries.is_empty() {
+    rules.insert(String::from("queries"), json!(request.queries));
+  }
+  if let Ok(body @ Value::Object(_)) = serde_json::from_slice(&request.body) {
+    rules.insert(String::from("body"), body);
+  }
+
+  let headers: Map<String, Value> = response
+    .headers
+    .iter()
+    .filter(|(name, _)| !strip.iter().any(|s| name.eq_ignore_ascii_case(s)))
+    .map(|(name, value)| (name.clone(), json!(value)))
+    .collect();
+  let mut recorded = json!({ "status": response.status, "headers": headers });
+  // A JSON body is kept readable, whereas any other body is kept as it is.
+  let is_json = response
+    .headers
+    .iter()
+    .any(|(name, value)| name.eq_ignore_ascii_case("Content-Type") && value.contains("json"));
+  match serde_json::from_slice::<Value>(&response.body) {
+    _ if response.body.is_empty() => {}
+    Ok(body) if is_json => recorded["body"] = body,
+    _ => recorded["bodyBase64"] = json!(base64::encode(&response.body)),
+  }
+
+  let mut condition = json!({ "method": request.method, "response": recorded });
+  if !rules.is_empty() {
+    condition["request"] = Value::Object(rules);
+  }
+  condition
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+  };
+
+  #[test]
+  fn relays_repeated_set_cookie_headers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut reader = BufReader::new(stream.try_clone().unwrap());
+      let mut line = String::new();
+      while reader.read_line(&mut line).unwrap() > 2 {
+        line.clear();
+      }
+      (&stream)
+        .write_all(b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1; Path=/\r\nSet-Cookie: b=2\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+    });
+
+    let target = Target {
+      host: String::from("127.0.0.1"),
+      port,
+      base_path: String::new(),
+    };
+    let request = Request {
+      method: String::from("GET"),
+      path: String::from("/projects/demo/login"),
+      query: String::new(),
+      version: String::from("HTTP/1.1"),
+      headers: HashMap::new(),
+      body: Vec::new(),
+      queries: HashMap::new(),
+      params: HashMap::new(),
+      matches: Vec::new(),
+    };
+    let response = forward(&target, "login", &request).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(
+      response.headers.get("Set-Cookie").unwrap(),
+      "a=1; Path=/\nb=2"
+    );
+    let condition = recorded_condition(&request, &response, &[]);
+    assert_eq!(
+      condition["response"]["headers"]["Set-Cookie"],
+      "a=1; Path=/\nb=2"
+    );
+  }
+}
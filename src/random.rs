@@ -0,0 +1,88 @@
+use std::{
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+};
+
+/// A small pseudo-random number generator (SplitMix64). It is not suitable for
+/// cryptography, but it is fast and can be seeded to reproduce a sequence.
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn seeded(seed: u64) -> Rng {
+    Rng { state: seed }
+  }
+
+  /// Returns a generator that only depends on a seed and a list of keys, so
+  /// that different keys get unrelated numbers from the same seed.
+  pub fn derived(seed: u64, keys: &[u64]) -> Rng {
+    keys.iter().fold(Rng::seeded(seed), |mut rng, key| {
+      Rng::seeded(rng.next_u64() ^ key)
+    })
+  }
+
+  /// Returns a generator seeded from the random keys of the standard library's
+  /// hash maps, which come from the operating system.
+  pub fn from_entropy() -> Rng {
+    Rng::seeded(RandomState::new().build_hasher().finish())
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Returns a number in `[0, 1)`.
+  pub fn next_f64(&mut self) -> f64 {
+    // The 53 high bits fill the mantissa of a double exactly.
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// Returns an integer in `[min, max]`.
+  pub fn range(&mut self, min: i64, max: i64) -> i64 {
+    if max <= min {
+      return min;
+    }
+    let span = (max as i128 - min as i128 + 1) as u128;
+    (min as i128 + (self.next_u64() as u128 % span) as i128) as i64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn seeded_generators_repeat() {
+    let mut a = Rng::seeded(42);
+    let mut b = Rng::seeded(42);
+
+    for _ in 0..10 {
+      assert_eq!(a.next_u64(), b.next_u64());
+    }
+  }
+
+  #[test]
+  fn derived_generators_depend_on_their_keys() {
+    let first = |keys: &[u64]| Rng::derived(42, keys).next_u64();
+
+    assert_eq!(first(&[1, 2]), first(&[1, 2]));
+    assert_ne!(first(&[1, 2]), first(&[2, 1]));
+    assert_ne!(first(&[1, 2]), first(&[1, 3]));
+  }
+
+  #[test]
+  fn range_is_inclusive() {
+    let mut rng = Rng::seeded(1);
+
+    for _ in 0..100 {
+      let value = rng.range(-2, 2);
+      assert!((-2..=2).contains(&value));
+    }
+    assert_eq!(rng.range(5, 5), 5);
+  }
+}
@@ -0,0 +1,90 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+/// The state every scenario starts in.
+pub const STARTED: &str = "Started";
+
+/// The current states of a project's scenarios, by name. A scenario that is
+/// missing is in the `Started` state.
+pub type States = HashMap<String, String>;
+
+/// The states of the scenarios of every project. They only live in memory, so
+/// they start over when the server restarts. Each project's states have their
+/// own lock, so that projects do not wait for each other.
+pub struct Scenarios {
+  projects: Mutex<HashMap<String, Arc<Mutex<States>>>>,
+}
+
+impl Scenarios {
+  pub fn new() -> Scenarios {
+    Scenarios {
+      projects: Mutex::new(HashMap::new()),
+    }
+  }
+
+  fn project(&self, project: &str) -> Arc<Mutex<States>> {
+    let mut projects = self.projects.lock().unwrap();
+    projects.entry(project.to_string()).or_default().clone()
+  }
+
+  pub fn states(&self, project: &str) -> States {
+    self.project(project).lock().unwrap().clone()
+  }
+
+  /// Moves a scenario to a state, as chosen from a snapshot of the project's
+  /// states, unless they changed since the snapshot. Returns whether the
+  /// snapshot was still current, so that the choice can be made again
+  /// otherwise.
+  pub fn transition(&self, project: &str, snapshot: &States, moved: Option<(&str, &str)>) -> bool {
+    let states = self.project(project);
+    let mut states = states.lock().unwrap();
+    if *states != *snapshot {
+      return false;
+    }
+
+    if let Some((scenario, state)) = moved {
+      states.insert(scenario.to_string(), state.to_string());
+    }
+    true
+  }
+
+  pub fn set(&self, project: &str, scenario: &str, state: &str) {
+    let states = self.project(project);
+    let mut states = states.lock().unwrap();
+    states.insert(scenario.to_string(), state.to_string());
+  }
+
+  /// Moves every scenario of a project back to the `Started` state.
+  pub fn reset(&self, project: &str) {
+    if let Some(states) = self.projects.lock().unwrap().remove(project) {
+      // A request that chose its condition from the previous states sees that
+      // they changed.
+      states.lock().unwrap().clear();
+    }
+  }
+}
+
+/// Returns the current state of a scenario.
+pub fn state_of<'a>(states: &'a States, scenario: &str) -> &'a str {
+  states.get(scenario).map_or(STARTED, String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn transitions_from_stale_snapshots_are_refused() {
+    let scenarios = Scenarios::new();
+    let snapshot = scenarios.states("shop");
+
+    assert!(scenarios.transition("shop", &snapshot, Some(("pay", "paid"))));
+    assert!(!scenarios.transition("shop", &snapshot, Some(("pay", "refunded"))));
+    assert_eq!(state_of(&scenarios.states("shop"), "pay"), "paid");
+
+    scenarios.reset("shop");
+    assert_eq!(state_of(&scenarios.states("shop"), "pay"), STARTED);
+  }
+}
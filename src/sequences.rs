@@ -0,0 +1,139 @@
+use crate::{
+  config::{Condition, MockResponse, Mode},
+  random::Rng,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+/// How many requests each condition of every project has answered, which
+/// tells the `sequence` and `cycle` modes which response comes next. The counts
+/// only live in memory.
+pub struct Sequences {
+  calls: Mutex<HashMap<String, Calls>>,
+}
+
+/// The number of requests answered by a project's conditions, by their
+/// endpoint's and their own index.
+type Calls = HashMap<(usize, usize), u64>;
+
+impl Sequences {
+  pub fn new() -> Sequences {
+    Sequences {
+      calls: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Counts a request answered by a condition, given by its indexes in the
+  /// project's config, and returns how many it answered before.
+  pub fn next(&self, project: &str, endpoint: usize, condition: usize) -> u64 {
+    let mut calls = self.calls.lock().unwrap();
+    let count = calls
+      .entry(project.to_string())
+      .or_default()
+      .entry((endpoint, condition))
+      .or_insert(0);
+    *count += 1;
+
+    *count - 1
+  }
+
+  /// Starts the sequences of a project's conditions over.
+  pub fn reset(&self, project: &str) {
+    self.calls.lock().unwrap().remove(project);
+  }
+}
+
+/// Picks the response of a condition for the request it answered after `call`
+/// others.
+pub fn pick<'a>(condition: &'a Condition, call: u64, rng: &mut Rng) -> &'a MockResponse {
+  let responses = &condition.responses;
+  let last = responses.len() as u64 - 1;
+
+  match condition.mode {
+    Mode::Sequence => &responses[call.min(last) as usize],
+    Mode::Cycle => &responses[(call % responses.len() as u64) as usize],
+    Mode::Random => {
+      // The config's weights add up to at most `u64::MAX`, but the sum must not
+      // panic on the ones built otherwise.
+      let total = responses
+        .iter()
+        .fold(0, |total: u64, r| total.saturating_add(r.weight));
+      let mut roll = rng.next_u64() % total;
+      for response in responses {
+        if roll < response.weight {
+          return response;
+        }
+        roll -= response.weight;
+      }
+      &responses[last as usize]
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ProjectConfig;
+  use serde_json::json;
+
+  fn condition(mode: &str, weights: &[u64]) -> Condition {
+    let responses: Vec<_> = weights
+      .iter()
+      .enumerate()
+      .map(|(i, weight)| json!({ "status": 200 + i, "weight": weight }))
+      .collect();
+    let config = ProjectConfig::from_value(&json!({ "endpoints": [{ "path": "a", "when": [{
+      "method": "GET", "mode": mode, "responses": responses,
+    }]}]}))
+    .unwrap();
+    config.endpoints[0].when[0].clone()
+  }
+
+  fn statuses(condition: &Condition, calls: u64) -> Vec<u16> {
+    let mut rng = Rng::seeded(1);
+    (0..calls)
+      .map(|call| pick(condition, call, &mut rng).status)
+      .collect()
+  }
+
+  #[test]
+  fn sequences_stick_to_the_last_response() {
+    assert_eq!(
+      statuses(&condition("sequence", &[1, 1, 1]), 5),
+      [200, 201, 202, 202, 202]
+    );
+  }
+
+  #[test]
+  fn cycles_start_over() {
+    assert_eq!(
+      statuses(&condition("cycle", &[1, 1]), 5),
+      [200, 201, 200, 201, 200]
+    );
+  }
+
+  #[test]
+  fn random_picks_follow_the_weights() {
+    let picked = statuses(&condition("random", &[3, 1]), 1000);
+    let first = picked.iter().filter(|&&status| status == 200).count();
+
+    assert!((650..850).contains(&first), "picked {} times", first);
+
+    let mut heavy = condition("random", &[1, 1]);
+    for response in heavy.responses.iter_mut() {
+      response.weight = u64::MAX;
+    }
+    assert_eq!(statuses(&heavy, 3).len(), 3);
+  }
+
+  #[test]
+  fn counts_calls_per_condition() {
+    let sequences = Sequences::new();
+
+    assert_eq!(sequences.next("demo", 0, 0), 0);
+    assert_eq!(sequences.next("demo", 0, 0), 1);
+    assert_eq!(sequences.next("demo", 0, 1), 0);
+    assert_eq!(sequences.next("other", 0, 0), 0);
+    sequences.reset("demo");
+    assert_eq!(sequences.next("demo", 0, 0), 0);
+  }
+}
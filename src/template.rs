@@ -0,0 +1,327 @@
+use crate::{matcher, random::Rng, web_server::types::Request};
+use serde_json::{Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What the expressions of a response template can refer to.
+///
+/// Expressions are written between double braces:
+///
+/// - `request.method`, `request.path` and `request.body`.
+/// - `request.params.<name>`, `request.queries.<name>` and
+///   `request.headers.<name>`, where header names are case-insensitive.
+/// - `request.body/<pointer>`, a JSON pointer into the request body.
+/// - the helpers `now`, `now "unix"`, `now "millis"`, `uuid` and
+///   `randomInt <min> <max>`.
+///
+/// Expressions that are not understood are left as they are.
+pub struct Context<'a> {
+  request: &'a Request,
+  body: Value,
+  rng: &'a mut Rng,
+}
+
+impl<'a> Context<'a> {
+  pub fn new(request: &'a Request, rng: &'a mut Rng) -> Context<'a> {
+    Context {
+      request,
+      body: matcher::parse_body(request),
+      rng,
+    }
+  }
+
+  /// Renders the strings of a JSON value. A string that consists of a single
+  /// expression is replaced by the expression's value, keeping its type.
+  pub fn render_json(&mut self, value: &Value) -> Value {
+    match value {
+      Value::String(text) => {
+        if let Some(expression) = single_expression(text) {
+          if let Some(value) = self.evaluate(expression) {
+            return value;
+          }
+        }
+        Value::String(self.render(text))
+      }
+      Value::Array(items) => Value::Array(items.iter().map(|v| self.render_json(v)).collect()),
+      Value::Object(fields) => Value::Object(
+        fields
+          .iter()
+          .map(|(k, v)| (k.clone(), self.render_json(v)))
+          .collect::<Map<String, Value>>(),
+      ),
+      value => value.clone(),
+    }
+  }
+
+  /// Renders the expressions of a text.
+  pub fn render(&mut self, template: &str) -> String {
+    self.render_text(template, false)
+  }
+
+  /// Renders the expressions of a header's value. Their values may come from
+  /// the request, decoded, so their line breaks are dropped: they would
+  /// otherwise start headers of the response.
+  pub fn render_header(&mut self, template: &str) -> String {
+    self.render_text(template, true)
+  }
+
+  fn render_text(&mut self, template: &str, single_line: bool) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+      let end = match rest[start..].find("}}") {
+        Some(end) => start + end,
+        None => break,
+      };
+      rendered.push_str(&rest[..start]);
+      match self.evaluate(&rest[start + 2..end]) {
+        Some(value) if single_line => rendered.extend(to_text(&value).split(['\r', '\n'])),
+        Some(value) => rendered.push_str(&to_text(&value)),
+        None => rendered.push_str(&rest[start..end + 2]),
+      }
+      rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+  }
+
+  /// Evaluates an expression, returning `None` when it is not understood and
+  /// `null` when it refers to something the request does not have.
+  fn evaluate(&mut self, expression: &str) -> Option<Value> {
+    let words = split_words(expression.trim());
+    let (name, args) = words.split_first()?;
+
+    match (name.as_str(), args) {
+      ("now", []) => Some(Value::String(format_rfc3339(SystemTime::now()))),
+      ("now", [format]) => {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        match format.trim_matches('"') {
+          "unix" => Some(Value::from(elapsed.as_secs())),
+          "millis" => Some(Value::from(elapsed.as_millis() as u64)),
+          _ => None,
+        }
+      }
+      ("uuid", []) => Some(Value::String(self.uuid())),
+      ("randomInt", args) if args.len() <= 2 => {
+        let min = args.first().map_or(Some(0), |v| v.parse().ok())?;
+        let max = args.get(1).map_or(Some(100), |v| v.parse().ok())?;
+        Some(Value::from(self.rng.range(min, max)))
+      }
+      (path, []) => self.lookup(path),
+      _ => None,
+    }
+  }
+
+  fn lookup(&self, path: &str) -> Option<Value> {
+    let request = self.request;
+    let text =
+      |value: Option<&String>| Some(value.map_or(Value::Null, |v| Value::String(v.clone())));
+
+    if let Some(pointer) = path.strip_prefix("request.body/") {
+      return Some(
+        self
+          .body
+          .pointer(&format!("/{}", pointer))
+          .cloned()
+          .unwrap_or(Value::Null),
+      );
+    }
+
+    match path.split_once('.')?.1.split_once('.') {
+      _ if !path.starts_with("request.") => None,
+      Some(("params", name)) => text(request.params.get(name)),
+      Some(("queries", name)) => text(request.queries.get(name)),
+      Some(("headers", name)) => Some(
+        request
+          .header(name)
+          .map_or(Value::Null, |v| Value::String(v.to_string())),
+      ),
+      Some(_) => None,
+      None => match path {
+        "request.method" => Some(Value::String(request.method.clone())),
+        "request.path" => Some(Value::String(request.path.clone())),
+        "request.body" => Some(Value::String(
+          String::from_utf8_lossy(&request.body).into_owned(),
+        )),
+        _ => None,
+      },
+    }
+  }
+
+  /// Returns a random (version 4) UUID.
+  fn uuid(&mut self) -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&self.rng.next_u64().to_be_bytes());
+    bytes[8..].copy_from_slice(&self.rng.next_u64().to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+      "{}-{}-{}-{}-{}",
+      &hex[..8],
+      &hex[8..12],
+      &hex[12..16],
+      &hex[16..20],
+      &hex[20..]
+    )
+  }
+}
+
+/// Returns the expression of a text that consists of a single expression.
+fn single_expression(text: &str) -> Option<&str> {
+  let expression = text.strip_prefix("{{")?.strip_suffix("}}")?;
+  if expression.contains("{{") || expression.contains("}}") {
+    None
+  } else {
+    Some(expression)
+  }
+}
+
+/// Splits an expression into words, keeping quoted strings together.
+fn split_words(expression: &str) -> Vec<String> {
+  let mut words = Vec::new();
+  let mut word = String::new();
+  let mut quoted = false;
+
+  for c in expression.chars() {
+    match c {
+      '"' => {
+        quoted = !quoted;
+        word.push(c);
+      }
+      c if c.is_whitespace() && !quoted => {
+        if !word.is_empty() {
+          words.push(std::mem::take(&mut word));
+        }
+      }
+      c => word.push(c),
+    }
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+
+  words
+}
+
+fn to_text(value: &Value) -> String {
+  match value {
+    Value::Null => String::new(),
+    Value::String(text) => text.clone(),
+    value => value.to_string(),
+  }
+}
+
+/// Formats a time as an RFC 3339 timestamp in UTC, e.g. `2023-02-01T08:30:00Z`.
+pub fn format_rfc3339(time: SystemTime) -> String {
+  let seconds = time
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs() as i64);
+  let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+  // Converts days since the epoch to a civil date, see
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use std::{collections::HashMap, time::Duration};
+
+  fn request() -> Request {
+    Request {
+      method: String::from("POST"),
+      path: String::from("/projects/shop/users/42"),
+      query: String::from("id=3"),
+      version: String::from("HTTP/1.1"),
+      headers: HashMap::from([(String::from("Authorization"), String::from("Bearer x"))]),
+      body: br#"{"user":{"id":7,"name":"Ann"}}"#.to_vec(),
+      queries: HashMap::from([(String::from("id"), String::from("3"))]),
+      params: HashMap::from([(String::from("id"), String::from("42"))]),
+      matches: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn renders_request_data() {
+    let request = request();
+    let mut rng = Rng::seeded(1);
+    let mut context = Context::new(&request, &mut rng);
+
+    assert_eq!(
+      context
+        .render("{{request.params.id}}-{{ request.queries.id }}-{{request.headers.authorization}}"),
+      "42-3-Bearer x"
+    );
+    assert_eq!(
+      context.render("{{request.queries.missing}}|{{unknown}}|{{"),
+      "|{{unknown}}|{{"
+    );
+    assert_eq!(
+      context.render_json(
+        &json!({ "id": "{{request.body/user/id}}", "name": "Hi {{request.body/user/name}}" })
+      ),
+      json!({ "id": 7, "name": "Hi Ann" })
+    );
+  }
+
+  #[test]
+  fn header_values_stay_on_their_line() {
+    let mut request = request();
+    request.queries.insert(
+      String::from("next"),
+      String::from("/\r\nSet-Cookie: admin=1"),
+    );
+    let mut rng = Rng::seeded(1);
+    let mut context = Context::new(&request, &mut rng);
+
+    assert_eq!(
+      context.render_header("{{request.queries.next}}\nb=2"),
+      "/Set-Cookie: admin=1\nb=2"
+    );
+  }
+
+  #[test]
+  fn renders_helpers() {
+    let request = request();
+    let mut rng = Rng::seeded(1);
+    let mut context = Context::new(&request, &mut rng);
+
+    let uuid = context.render("{{uuid}}");
+    assert_eq!(uuid.len(), 36);
+    assert_eq!(&uuid[14..15], "4");
+
+    let number = context.render_json(&json!("{{randomInt 5 6}}"));
+    assert!(number == json!(5) || number == json!(6));
+  }
+
+  #[test]
+  fn formats_timestamps() {
+    let time = UNIX_EPOCH + Duration::from_secs(1675240200);
+
+    assert_eq!(format_rfc3339(time), "2023-02-01T08:30:00Z");
+    assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+  }
+}
@@ -1,19 +1,26 @@
 use std::{
   collections::HashMap,
-  io::Write,
+  io::BufReader,
   net::{TcpListener, TcpStream},
-  sync::{Arc, Mutex},
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 
+pub mod client;
 mod helpers;
 mod thread_pool;
 pub mod types;
 
 use types::{Request, Response};
 
+pub(crate) use helpers::is_token;
+pub use helpers::{decode_uri_component, insert_header, reason_phrase};
 pub use thread_pool::ThreadPool;
 
-use self::types::{Method, Nested, RequestOption, RequestPathPattern};
+use self::types::{Connection, Fault, Limits, Method, Nested, RequestOption, RequestPathPattern};
 
 pub struct Listener {
   path: RequestPathPattern,
@@ -21,26 +28,51 @@ pub struct Listener {
   handler: Handler,
 }
 
-type Handler = Box<dyn Fn(Request) -> Response + Send + 'static>;
+// Handlers are shared by every worker thread, so they must be `Sync` as well.
+type Handler = Box<dyn Fn(Request) -> Response + Send + Sync + 'static>;
 
 pub struct Server {
   max_connections: usize,
-  connection_handler: Arc<Mutex<ConnectionHandler>>,
+  connection_handler: ConnectionHandler,
 }
 
 pub struct ServerConf {
   pub max_connections: usize,
+  /// How long a persistent connection may stay idle before it is closed.
+  pub keep_alive_timeout: Duration,
+  /// How many connections may be kept alive at once. An idle connection holds
+  /// a worker until it times out, so this stays below `max_connections`,
+  /// leaving workers to the other connections.
+  pub max_keep_alive_connections: usize,
+  /// How many requests a persistent connection may serve before it is closed.
+  pub max_requests_per_connection: usize,
+  pub limits: Limits,
+}
+
+impl Default for ServerConf {
+  fn default() -> Self {
+    ServerConf {
+      max_connections: 1000,
+      keep_alive_timeout: Duration::from_secs(5),
+      max_keep_alive_connections: 750,
+      max_requests_per_connection: 1000,
+      limits: Limits::default(),
+    }
+  }
 }
 
 impl Server {
   pub fn new(conf: ServerConf) -> Server {
     Server {
       max_connections: conf.max_connections,
-      connection_handler: Arc::new(Mutex::new(ConnectionHandler::new())),
+      connection_handler: ConnectionHandler::new(conf),
     }
   }
 
-  pub fn listen(&self, addr: String) {
+  /// Starts accepting connections. The listeners can not change afterwards, so
+  /// the server shares them between the worker threads without any locking and
+  /// the connections are handled in parallel.
+  pub fn listen(self, addr: String) {
     // Some possible reasons for binding to fail:
     // - connecting to a port requires administrator privileges.
     // - listening to a port which is occupied.
@@ -49,6 +81,7 @@ impl Server {
     // Limit the number of threads in the pool to a small number to protect us
     // from Denial of Service (DoS) attacks.
     let pool = ThreadPool::new(self.max_connections);
+    let connection_handler = Arc::new(self.connection_handler);
 
     for stream in listener.incoming() {
       // The browser signals the end of an HTTP request by sending two newline
@@ -63,10 +96,9 @@ impl Server {
       // some of the open connections are closed.
       let stream = stream.unwrap();
 
-      let connection_handler = self.connection_handler.clone();
+      let connection_handler = Arc::clone(&connection_handler);
 
       pool.execute(move || {
-        let connection_handler = connection_handler.lock().unwrap();
         connection_handler.handle_connection(stream);
       });
     }
@@ -74,11 +106,9 @@ impl Server {
 
   pub fn request<F>(&mut self, request_handler: F, option: RequestOption)
   where
-    F: Fn(Request) -> Response + Send + 'static,
+    F: Fn(Request) -> Response + Send + Sync + 'static,
   {
-    let mut connection_handler = self.connection_handler.lock().unwrap();
-
-    connection_handler.listeners.push(Listener {
+    self.connection_handler.listeners.push(Listener {
       method: option.method,
       path: option.path,
       handler: Box::new(request_handler),
@@ -87,7 +117,7 @@ impl Server {
 
   pub fn get<F>(&mut self, path: &str, request_handler: F)
   where
-    F: Fn(Request) -> Response + Send + 'static,
+    F: Fn(Request) -> Response + Send + Sync + 'static,
   {
     self.request(
       request_handler,
@@ -100,7 +130,7 @@ impl Server {
 
   pub fn post<F>(&mut self, path: &str, request_handler: F)
   where
-    F: Fn(Request) -> Response + Send + 'static,
+    F: Fn(Request) -> Response + Send + Sync + 'static,
   {
     self.request(
       request_handler,
@@ -113,7 +143,7 @@ impl Server {
 
   pub fn put<F>(&mut self, path: &str, request_handler: F)
   where
-    F: Fn(Request) -> Response + Send + 'static,
+    F: Fn(Request) -> Response + Send + Sync + 'static,
   {
     self.request(
       request_handler,
@@ -123,11 +153,37 @@ impl Server {
       },
     );
   }
+
+  pub fn patch<F>(&mut self, path: &str, request_handler: F)
+  where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+  {
+    self.request(
+      request_handler,
+      RequestOption {
+        path: RequestPathPattern::Exact(String::from(path)),
+        method: Method::Patch,
+      },
+    );
+  }
+
+  pub fn delete<F>(&mut self, path: &str, request_handler: F)
+  where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+  {
+    self.request(
+      request_handler,
+      RequestOption {
+        path: RequestPathPattern::Exact(String::from(path)),
+        method: Method::Delete,
+      },
+    );
+  }
 }
 
 impl Response {
   pub fn json(status: u16, body: Nested, headers: Option<HashMap<String, String>>) -> Response {
-    let mut headers = headers.unwrap_or(HashMap::new());
+    let mut headers = headers.unwrap_or_default();
 
     headers.insert(
       String::from("Content-Type"),
@@ -136,77 +192,207 @@ impl Response {
 
     Response {
       status,
-      body: helpers::stringify_nested(&body),
+      body: helpers::stringify_nested(&body).into_bytes(),
       headers,
+      fault: None,
     }
   }
 
-  pub fn ok(body: String, headers: Option<HashMap<String, String>>) -> Response {
-    let mut headers = headers.unwrap_or(HashMap::new());
+  pub fn ok(body: impl Into<Vec<u8>>, headers: Option<HashMap<String, String>>) -> Response {
+    let mut headers = headers.unwrap_or_default();
 
-    if headers.get("Content-Type").is_none() {
+    if !headers.contains_key("Content-Type") {
       headers.insert(String::from("Content-Type"), String::from("text/plain"));
     }
 
     Response {
       status: 200,
-      body,
+      body: body.into(),
       headers,
+      fault: None,
     }
   }
 }
 
+impl Request {
+  /// Returns the value of a header, ignoring the case of its name.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+}
+
+/// Counts the connections that are kept alive, up to a maximum.
+struct KeepAliveSlots {
+  max: usize,
+  taken: AtomicUsize,
+}
+
+/// A connection's permission to be kept alive, given back when it is dropped.
+struct KeepAliveSlot<'a>(&'a AtomicUsize);
+
+impl KeepAliveSlots {
+  fn take(&self) -> Option<KeepAliveSlot<'_>> {
+    self
+      .taken
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |taken| {
+        (taken < self.max).then_some(taken + 1)
+      })
+      .ok()
+      .map(|_| KeepAliveSlot(&self.taken))
+  }
+}
+
+impl Drop for KeepAliveSlot<'_> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
 struct ConnectionHandler {
   listeners: Vec<Listener>,
+  keep_alive_timeout: Duration,
+  keep_alive_slots: KeepAliveSlots,
+  max_requests_per_connection: usize,
+  limits: Limits,
 }
 
 impl ConnectionHandler {
-  pub fn new() -> ConnectionHandler {
+  pub fn new(conf: ServerConf) -> ConnectionHandler {
     ConnectionHandler {
       listeners: Vec::new(),
+      keep_alive_timeout: conf.keep_alive_timeout,
+      keep_alive_slots: KeepAliveSlots {
+        max: conf
+          .max_keep_alive_connections
+          .min(conf.max_connections.saturating_sub(1)),
+        taken: AtomicUsize::new(0),
+      },
+      max_requests_per_connection: conf.max_requests_per_connection,
+      limits: conf.limits,
     }
   }
 
-  pub fn handle_connection(&self, mut stream: TcpStream) {
-    let mut request = helpers::parse_tcp_stream(&mut stream).unwrap();
+  /// Serves the requests of a connection one after the other until the client
+  /// closes it, asks to close it, stays idle for too long or reaches the maximum
+  /// number of requests. When too many connections are kept alive already, the
+  /// connection is closed after its first response. Pipelined requests are
+  /// buffered by the reader, so they are answered in the order they were sent.
+  pub fn handle_connection(&self, stream: TcpStream) {
+    if stream
+      .set_read_timeout(Some(self.keep_alive_timeout))
+      .is_err()
+    {
+      return;
+    }
+    let mut reader = match stream.try_clone() {
+      Ok(stream) => BufReader::new(stream),
+      Err(_) => return,
+    };
+    let mut writer = stream;
+    let mut slot = None;
+
+    for served in 1.. {
+      let request = match helpers::parse_request(&mut reader, &mut writer, &self.limits) {
+        Ok(Some(request)) => request,
+        // Stop when the client has closed the connection.
+        Ok(None) => break,
+        // An idle timeout arrives as an I/O error without a status, so the
+        // connection is closed without a response.
+        Err(error) => {
+          if let Some(status) = error.status() {
+            let mut headers = HashMap::new();
+            headers.insert(String::from("Content-Type"), String::from("text/plain"));
+            let response = Response {
+              status,
+              body: error.to_string().into_bytes(),
+              headers,
+              fault: None,
+            };
+            if helpers::write_response(&mut writer, response, false, Connection::Close).is_ok() {
+              helpers::linger(&writer, &mut reader);
+            }
+          }
+          break;
+        }
+      };
+
+      let is_head = request.method == "HEAD";
+      let wants_keep_alive =
+        helpers::wants_keep_alive(&request) && served < self.max_requests_per_connection;
+      let response = self.dispatch(request);
+
+      // Most faults leave the connection unusable, so it is closed afterwards.
+      let fault = response.fault;
+      let mut keep_alive = wants_keep_alive && fault.is_none_or(|f| f.keeps_connection());
+      if keep_alive && slot.is_none() {
+        slot = self.keep_alive_slots.take();
+        keep_alive = slot.is_some();
+      }
+      if fault == Some(Fault::Reset) {
+        helpers::reset(&writer);
+        break;
+      }
 
-    let mut response_status = 404;
-    let mut response_body = String::new();
-    let mut response_headers = String::new();
+      let connection = if keep_alive {
+        Connection::KeepAlive(self.keep_alive_timeout)
+      } else {
+        Connection::Close
+      };
+      if helpers::write_response(&mut writer, response, is_head, connection).is_err() || !keep_alive
+      {
+        break;
+      }
+    }
+  }
 
+  /// Passes a request to the first listener that accepts it.
+  fn dispatch(&self, mut request: Request) -> Response {
     for listener in self.listeners.iter() {
-      let parsed_path = helpers::parse_request_path(&listener.path, &request.path[..]);
-
-      if parsed_path.is_some() && listener.method.to_string() == request.method {
-        let handler = &listener.handler;
+      if !listener.method.matches(&request.method) {
+        continue;
+      }
 
-        let parsed_path = parsed_path.unwrap();
+      if let Some(parsed_path) = helpers::parse_request_path(&listener.path, &request.path[..]) {
         request.path = parsed_path.path;
+        request.query = parsed_path.query;
         request.queries = parsed_path.queries;
         request.params = parsed_path.params;
         request.matches = parsed_path.matches;
 
-        let response = handler(request);
-        response_status = response.status;
-        response_body = response.body;
-
-        if response.headers.len() > 0 {
-          for (key, value) in response.headers.iter() {
-            response_headers.push_str(&format!("{}: {}\r\n", key, value));
-          }
-        }
-        break;
+        return (listener.handler)(request);
       }
     }
 
-    let length = response_body.len();
-    let response = format!(
-      "HTTP/1.1 {response_status}\r\n{response_headers}Content-Length: {length}\r\n\r\n{response_body}"
-    );
+    Response {
+      status: 404,
+      body: Vec::new(),
+      headers: HashMap::new(),
+      fault: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keep_alive_slots_are_limited_and_given_back() {
+    let slots = KeepAliveSlots {
+      max: 2,
+      taken: AtomicUsize::new(0),
+    };
+
+    let first = slots.take();
+    let second = slots.take();
+    assert!(first.is_some() && second.is_some());
+    assert!(slots.take().is_none());
 
-    // The write_all method on stream takes a &[u8] and sends those bytes directly
-    // down the connection.
-    stream.write_all(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    drop(first);
+    assert!(slots.take().is_some());
   }
 }
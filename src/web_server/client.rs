@@ -0,0 +1,191 @@
+use std::{
+  io::{BufRead, BufReader, Read, Write},
+  net::{TcpStream, ToSocketAddrs},
+  time::Duration,
+};
+
+use super::{
+  helpers,
+  types::{Limits, ParseError, Request, Response},
+};
+
+/// The headers that only concern a single connection, which are not passed on
+/// to the other side, along with the ones that frame the message.
+const HOP_BY_HOP_HEADERS: [&str; 11] = [
+  "Connection",
+  "Keep-Alive",
+  "Proxy-Authenticate",
+  "Proxy-Authorization",
+  "TE",
+  "Trailer",
+  "Transfer-Encoding",
+  "Upgrade",
+  "Content-Length",
+  "Expect",
+  "Host",
+];
+
+/// Returns whether a header only concerns a single connection.
+pub fn is_hop_by_hop(name: &str) -> bool {
+  HOP_BY_HOP_HEADERS
+    .iter()
+    .any(|h| name.eq_ignore_ascii_case(h))
+}
+
+/// Sends a request to an HTTP/1.1 server and reads its response, on a new
+/// connection that is closed afterwards. The request's `path` is sent as the
+/// request target, so it has to include the query string.
+pub fn send(
+  host: &str,
+  port: u16,
+  request: &Request,
+  timeout: Duration,
+  limits: &Limits,
+) -> Result<Response, ParseError> {
+  let address = (host, port)
+    .to_socket_addrs()
+    .map_err(ParseError::Io)?
+    .next()
+    .ok_or(ParseError::BadRequest("Unknown host."))?;
+  let stream = TcpStream::connect_timeout(&address, timeout).map_err(ParseError::Io)?;
+  stream
+    .set_read_timeout(Some(timeout))
+    .and_then(|_| stream.set_write_timeout(Some(timeout)))
+    .map_err(ParseError::Io)?;
+
+  let mut head = format!("{} {} HTTP/1.1\r\n", request.method, request.path);
+  if port == 80 {
+    head.push_str(&format!("Host: {}\r\n", host));
+  } else {
+    head.push_str(&format!("Host: {}:{}\r\n", host, port));
+  }
+  for (key, value) in request.headers.iter() {
+    if !is_hop_by_hop(key) {
+      helpers::push_header(&mut head, key, value);
+    }
+  }
+  head.push_str("Connection: close\r\n");
+  head.push_str(&format!("Content-Length: {}\r\n\r\n", request.body.len()));
+
+  let mut bytes = head.into_bytes();
+  bytes.extend_from_slice(&request.body);
+  let mut writer = stream.try_clone().map_err(ParseError::Io)?;
+  writer
+    .write_all(&bytes)
+    .and_then(|_| writer.flush())
+    .map_err(ParseError::Io)?;
+
+  read_response(
+    &mut BufReader::new(stream),
+    request.method == "HEAD",
+    limits,
+  )
+}
+
+/// Reads a response, skipping the interim `1xx` ones.
+fn read_response<R: BufRead>(
+  reader: &mut R,
+  is_head: bool,
+  limits: &Limits,
+) -> Result<Response, ParseError> {
+  loop {
+    let mut status_line = String::new();
+    reader
+      .by_ref()
+      .take(limits.max_request_line_length as u64)
+      .read_line(&mut status_line)
+      .map_err(ParseError::Io)?;
+    let status = match status_line.split(' ').collect::<Vec<&str>>()[..] {
+      [version, status, ..] if version.starts_with("HTTP/1.") => status.trim().parse::<u16>().ok(),
+      _ => None,
+    }
+    .filter(|status| (100..=599).contains(status))
+    .ok_or(ParseError::BadRequest("Malformed status line."))?;
+
+    let mut headers = helpers::read_headers(reader, limits)?;
+    if (100..200).contains(&status) {
+      continue;
+    }
+
+    let header = |name: &str| {
+      headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+    };
+    let has_body = !is_head && status != 204 && status != 304;
+    let body = if !has_body {
+      Vec::new()
+    } else if header("Transfer-Encoding").is_some_and(|e| e.to_lowercase().ends_with("chunked")) {
+      let (body, trailers) = helpers::read_chunked(reader, limits)?;
+      for (key, value) in trailers {
+        headers.entry(key).or_insert(value);
+      }
+      body
+    } else if let Some(length) = header("Content-Length") {
+      let length = length
+        .parse::<usize>()
+        .map_err(|_| ParseError::BadRequest("Malformed Content-Length."))?;
+      if length > limits.max_body_size {
+        return Err(ParseError::PayloadTooLarge);
+      }
+      let mut body = vec![0; length];
+      reader.read_exact(&mut body).map_err(ParseError::Io)?;
+      body
+    } else {
+      // Without framing, the body ends with the connection.
+      let mut body = Vec::new();
+      reader
+        .take(limits.max_body_size as u64)
+        .read_to_end(&mut body)
+        .map_err(ParseError::Io)?;
+      body
+    };
+
+    headers.retain(|key, _| !is_hop_by_hop(key));
+    return Ok(Response {
+      status,
+      body,
+      headers,
+      fault: None,
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read(response: &str) -> Response {
+    read_response(&mut response.as_bytes(), false, &Limits::default()).unwrap()
+  }
+
+  #[test]
+  fn reads_framed_responses() {
+    let response = read("HTTP/1.1 201 Created\r\nContent-Length: 2\r\nX-Id: 1\r\n\r\nokextra");
+
+    assert_eq!(response.status, 201);
+    assert_eq!(response.body, b"ok");
+    assert_eq!(response.headers.get("X-Id").unwrap(), "1");
+    assert!(!response.headers.contains_key("Content-Length"));
+  }
+
+  #[test]
+  fn reads_chunked_and_unframed_responses() {
+    let response = read(
+      "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\n",
+    );
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"abc");
+
+    let response = read("HTTP/1.0 200 OK\r\n\r\nuntil the end");
+    assert_eq!(response.body, b"until the end");
+  }
+
+  #[test]
+  fn keeps_set_cookie_headers_apart() {
+    let response = read("HTTP/1.1 204 No Content\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n");
+
+    assert_eq!(response.headers.get("Set-Cookie").unwrap(), "a=1\nb=2");
+  }
+}
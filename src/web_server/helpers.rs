@@ -2,11 +2,16 @@ use regex::Regex;
 
 use std::{
   collections::HashMap,
-  io::{BufRead, BufReader, Error as IoError, Read},
-  net::TcpStream,
+  io::{self, BufRead, Error as IoError, ErrorKind, Read, Write},
+  net::{Shutdown, TcpStream},
+  thread,
+  time::Duration,
 };
 
-use super::types::{Nested, NestedValue, Request, RequestPath, RequestPathPattern};
+use super::types::{
+  Connection, Fault, Limits, Nested, NestedValue, ParseError, PathPattern, Request, RequestPath,
+  RequestPathPattern, Response, Segment,
+};
 
 /// Converts a [Nested] to a JSON string.
 pub fn stringify_nested(nested: &Nested) -> String {
@@ -32,47 +37,67 @@ fn stringfy_nested_value(nested: &NestedValue) -> String {
   }
 }
 
+/// Decodes the percent-encoded octets of a URI component. Invalid escapes are
+/// kept as they are.
+pub fn decode_uri_component(component: &str) -> String {
+  let bytes = component.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+
+  while i < bytes.len() {
+    if bytes[i] == b'%' && i + 2 < bytes.len() {
+      let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+      if let Ok(byte) = u8::from_str_radix(hex, 16) {
+        decoded.push(byte);
+        i += 3;
+        continue;
+      }
+    }
+    decoded.push(bytes[i]);
+    i += 1;
+  }
+
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
 /// Parses the parameters in a path.
 pub fn parse_request_path(
   path_pattern: &RequestPathPattern,
   mut request_path: &str,
 ) -> Option<RequestPath> {
   let mut queries = HashMap::new();
-  let query_string_starts = request_path.find('?');
-
-  if query_string_starts.is_some() {
-    let query_string_starts = query_string_starts.unwrap();
-    let query_string = request_path[query_string_starts + 1..].to_string();
+  let mut query_string = String::new();
+  if let Some(query_string_starts) = request_path.find('?') {
+    query_string = request_path[query_string_starts + 1..].to_string();
     request_path = &request_path[..query_string_starts];
     queries = query_string
       .split('&')
-      .map(|s| s.split('=').collect::<Vec<&str>>())
-      .filter(|v| v.len() == 2)
-      .map(|v| (v[0].to_string(), v[1].to_string()))
+      .filter(|s| !s.is_empty())
+      .map(|s| s.split_once('=').unwrap_or((s, "")))
+      .map(|(k, v)| {
+        (
+          decode_uri_component(&k.replace('+', " ")),
+          decode_uri_component(&v.replace('+', " ")),
+        )
+      })
       .collect();
   }
 
   match path_pattern {
     RequestPathPattern::Exact(path_pattern) => {
-      let mut params = HashMap::new();
-      let pattern_segments: Vec<&str> = path_pattern.split("/").collect();
-      let request_segments: Vec<&str> = request_path.split("/").collect();
-
-      if pattern_segments.len() != request_segments.len() {
+      let params = PathPattern::parse(path_pattern)
+        .ok()?
+        .matches(request_path)?;
+      // The parameters of routes name files and the like, so an encoded one
+      // must not move to another directory.
+      let escapes = |value: &String| value.contains(['/', '\\']) || value == "." || value == "..";
+      if params.values().any(escapes) {
         return None;
       }
 
-      for (pattern, request) in pattern_segments.iter().zip(request_segments.iter()) {
-        if pattern.starts_with(":") {
-          let key = pattern[1..].to_string();
-          params.insert(key, request.to_string());
-        } else if pattern != request {
-          return None;
-        }
-      }
-
       Some(RequestPath {
         path: request_path.to_string(),
+        query: query_string,
         queries,
         params,
         matches: Vec::new(),
@@ -90,7 +115,8 @@ pub fn parse_request_path(
         }
         Some(RequestPath {
           path: request_path.to_string(),
-          queries: HashMap::new(),
+          query: query_string,
+          queries,
           params: HashMap::new(),
           matches,
         })
@@ -132,56 +158,952 @@ mod tests {
 
     assert_eq!(result, None);
   }
+
+  #[test]
+  fn params_must_not_be_empty() {
+    let result = parse_request_path(
+      &RequestPathPattern::Exact(String::from("/projects/:name")),
+      "/projects/",
+    );
+
+    assert_eq!(result, None);
+  }
+
+  #[test]
+  fn route_params_must_not_escape_their_segment() {
+    for path in [
+      "/projects/..%2F..",
+      "/projects/a%5Cb",
+      "/projects/..",
+      "/projects/%2E%2E",
+    ] {
+      let result = parse_request_path(
+        &RequestPathPattern::Exact(String::from("/projects/:name")),
+        path,
+      );
+      assert_eq!(result, None, "matched {}", path);
+    }
+  }
+
+  #[test]
+  fn wildcards_and_regex_segments() {
+    let pattern = PathPattern::parse("users/:id(\\d+)/*/files/**").unwrap();
+
+    let params = pattern
+      .matches("users/42/avatars/files/a/b%20c.png")
+      .unwrap();
+    assert_eq!(params.get("id").unwrap(), "42");
+    assert_eq!(params.get("0").unwrap(), "avatars");
+    assert_eq!(params.get("1").unwrap(), "a/b c.png");
+    assert_eq!(
+      pattern
+        .matches("users/42/x/files")
+        .unwrap()
+        .get("1")
+        .unwrap(),
+      ""
+    );
+    assert_eq!(pattern.matches("users/ann/x/files/a"), None);
+    assert_eq!(pattern.matches("users/42//files/a"), None);
+
+    assert!(PathPattern::parse("users/:id(\\d+").is_err());
+    assert!(PathPattern::parse("users/:id([)").is_err());
+  }
+
+  #[test]
+  fn queries_are_decoded() {
+    let result = parse_request_path(
+      &RequestPathPattern::Match(String::from("^/projects/([^/]+)")),
+      "/projects/foo?name=a%20b+c&flag&x=%E2%9C%93",
+    )
+    .unwrap();
+
+    assert_eq!(result.queries.get("name").unwrap(), "a b c");
+    assert_eq!(result.queries.get("flag").unwrap(), "");
+    assert_eq!(result.queries.get("x").unwrap(), "✓");
+  }
+
+  #[test]
+  fn pipelined_requests_are_parsed_in_order() {
+    let mut reader = std::io::Cursor::new(
+      "GET /a HTTP/1.1\r\nHost: x\r\n\r\nPOST /b HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi\r\n"
+        .as_bytes(),
+    );
+
+    let first = parse_request(&mut reader, &mut io::sink(), &Limits::default())
+      .unwrap()
+      .unwrap();
+    let second = parse_request(&mut reader, &mut io::sink(), &Limits::default())
+      .unwrap()
+      .unwrap();
+
+    assert_eq!(first.path, "/a");
+    assert_eq!(second.path, "/b");
+    assert_eq!(second.body, b"hi");
+    assert!(
+      parse_request(&mut reader, &mut io::sink(), &Limits::default())
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn http_1_0_connections_are_closed_by_default() {
+    let mut reader =
+      std::io::Cursor::new("GET / HTTP/1.0\r\n\r\nGET / HTTP/1.1\r\n\r\n".as_bytes());
+
+    let first = parse_request(&mut reader, &mut io::sink(), &Limits::default())
+      .unwrap()
+      .unwrap();
+    let second = parse_request(&mut reader, &mut io::sink(), &Limits::default())
+      .unwrap()
+      .unwrap();
+
+    assert!(!wants_keep_alive(&first));
+    assert!(wants_keep_alive(&second));
+  }
+
+  fn parse(request: &str) -> Result<Option<Request>, ParseError> {
+    let mut interim = Vec::new();
+    let limits = Limits {
+      max_request_line_length: 32,
+      max_header_count: 2,
+      max_header_size: 64,
+      max_body_size: 8,
+    };
+    parse_request(
+      &mut std::io::Cursor::new(request.as_bytes()),
+      &mut interim,
+      &limits,
+    )
+  }
+
+  fn status(request: &str) -> Option<u16> {
+    parse(request).err().and_then(|e| e.status())
+  }
+
+  #[test]
+  fn malformed_requests_are_rejected() {
+    assert_eq!(status("GET\r\n\r\n"), Some(400));
+    assert_eq!(status("GET / HTTP/1.1\r\nHost x\r\n\r\n"), Some(400));
+    assert_eq!(status("GET / HTTP/1.1\r\nHost : x\r\n\r\n"), Some(400));
+    assert_eq!(status("GET / HTTP/1.1\r\nHost: x\r\n"), Some(400));
+    assert_eq!(status("GET / HTTP/2.0\r\n\r\n"), Some(505));
+    assert_eq!(status("GET / FTP/1.0\r\n\r\n"), Some(400));
+    assert_eq!(
+      status("POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nab"),
+      Some(400)
+    );
+  }
+
+  #[test]
+  fn limits_are_enforced() {
+    assert_eq!(
+      status("GET /a-very-long-path-indeed HTTP/1.1\r\n\r\n"),
+      Some(414)
+    );
+    assert_eq!(
+      status("GET / HTTP/1.1\r\nA: 1\r\nA: 2\r\nA: 3\r\n\r\n"),
+      Some(431)
+    );
+    assert_eq!(
+      status(&format!("GET / HTTP/1.1\r\nA: {}\r\n\r\n", "x".repeat(64))),
+      Some(431)
+    );
+    assert_eq!(
+      status("POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\n"),
+      Some(413)
+    );
+  }
+
+  #[test]
+  fn binary_bodies_are_kept_as_they_are() {
+    let mut request = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\n".to_vec();
+    request.extend_from_slice(&[0, 0xff, 0xfe]);
+    let request = parse_request(
+      &mut std::io::Cursor::new(request),
+      &mut io::sink(),
+      &Limits::default(),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(request.body, [0, 0xff, 0xfe]);
+  }
+
+  #[test]
+  fn chunked_bodies_are_decoded() {
+    let request = parse(
+      "DELETE / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n3;ext=1\r\nabc\r\n2\r\nde\r\n0\r\nX-Sum: 1\r\n\r\n",
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(request.body, b"abcde");
+    assert_eq!(request.header("X-Sum"), Some("1"));
+    assert_eq!(
+      status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n9\r\n"),
+      Some(413)
+    );
+    assert_eq!(
+      status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n"),
+      Some(400)
+    );
+  }
+
+  #[test]
+  fn oversized_chunk_sizes_are_rejected() {
+    assert_eq!(
+      status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n"),
+      Some(413)
+    );
+    assert_eq!(
+      status(
+        "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\nffffffffffffffff\r\n"
+      ),
+      Some(413)
+    );
+  }
+
+  #[test]
+  fn continue_is_sent_before_the_body() {
+    let mut reader = std::io::Cursor::new(
+      "PATCH / HTTP/1.1\r\nExpect: 100-continue\r\ncontent-length: 2\r\n\r\nhi".as_bytes(),
+    );
+    let mut interim = Vec::new();
+
+    let request = parse_request(&mut reader, &mut interim, &Limits::default())
+      .unwrap()
+      .unwrap();
+
+    assert_eq!(request.body, b"hi");
+    assert_eq!(interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    assert_eq!(
+      status("PUT / HTTP/1.1\r\nExpect: x\r\nContent-Length: 1\r\n\r\na"),
+      Some(417)
+    );
+  }
+
+  #[test]
+  fn repeated_set_cookie_headers_stay_apart() {
+    let head = "Accept: a\r\nSet-Cookie: a=1; Expires=Mon, 01 Jan 2024\r\nAccept: b\r\nSet-Cookie: b=2\r\n\r\n";
+    let headers = read_headers(&mut head.as_bytes(), &Limits::default()).unwrap();
+    assert_eq!(headers.get("Accept").unwrap(), "a, b");
+
+    let response = Response {
+      status: 200,
+      body: Vec::new(),
+      headers,
+      fault: None,
+    };
+    let mut output = Vec::new();
+    write_response(&mut output, response, false, Connection::Close).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("\r\nSet-Cookie: a=1; Expires=Mon, 01 Jan 2024\r\nSet-Cookie: b=2\r\n"));
+  }
+
+  #[test]
+  fn keep_alive_timeouts_are_rounded_up() {
+    let response = Response {
+      status: 204,
+      body: Vec::new(),
+      headers: HashMap::new(),
+      fault: None,
+    };
+    let mut output = Vec::new();
+    let connection = Connection::KeepAlive(Duration::from_millis(500));
+    write_response(&mut output, response, false, connection).unwrap();
+
+    assert!(String::from_utf8(output)
+      .unwrap()
+      .contains("\r\nKeep-Alive: timeout=1\r\n"));
+  }
+
+  #[test]
+  fn faults_are_written() {
+    let written = |fault| {
+      let response = Response {
+        status: 200,
+        body: b"abcdef".to_vec(),
+        headers: HashMap::new(),
+        fault,
+      };
+      let mut output = Vec::new();
+      write_response(&mut output, response, false, Connection::Close).unwrap();
+      String::from_utf8_lossy(&output).into_owned()
+    };
+
+    let truncated = written(Some(Fault::Truncate));
+    assert!(truncated.contains("Content-Length: 6\r\n"));
+    assert!(truncated.ends_with("\r\n\r\nabc"));
+    assert_eq!(written(Some(Fault::Close)), "");
+    assert!(!written(Some(Fault::Malformed)).starts_with("HTTP/1.1 200"));
+
+    let dripped = written(Some(Fault::Drip {
+      chunk_size: 4,
+      interval: Duration::ZERO,
+    }));
+    assert_eq!(dripped, written(None));
+  }
 }
 
-pub fn parse_tcp_stream(stream: &mut TcpStream) -> Result<Request, IoError> {
-  let mut buf_reader = BufReader::new(stream);
-  let mut start_line = String::new();
-  buf_reader.read_line(&mut start_line)?;
+impl PathPattern {
+  /// Parses a pattern whose segments are separated by `/`. Each segment is one
+  /// of:
+  ///
+  /// - a literal, which has to be equal to the request's segment.
+  /// - `:name`, which captures a non-empty segment as the parameter `name`.
+  /// - `:name(regex)`, which also requires the segment to match `regex`.
+  /// - `*`, which captures a non-empty segment.
+  /// - `**`, which captures any number of segments, including none.
+  ///
+  /// The wildcards are captured as the parameters `0`, `1`, etc.
+  pub fn parse(pattern: &str) -> Result<PathPattern, String> {
+    let mut segments = Vec::new();
+    let mut wildcards = 0;
 
-  let mut start_line_parts = start_line.split_whitespace();
-  let method = start_line_parts.next().unwrap().to_uppercase();
-  let path = start_line_parts.next().unwrap().to_owned();
-  let version = start_line_parts.next().unwrap().to_owned();
+    for segment in pattern.split('/') {
+      let segment = match segment {
+        "*" | "**" => {
+          let name = wildcards.to_string();
+          wildcards += 1;
+          if segment == "*" {
+            Segment::Wildcard(name)
+          } else {
+            Segment::Rest(name)
+          }
+        }
+        _ => match segment.strip_prefix(':') {
+          Some(param) => match param.split_once('(') {
+            Some((name, regex)) => {
+              let regex = regex
+                .strip_suffix(')')
+                .ok_or_else(|| format!("Unclosed regex in segment `{}`.", segment))?;
+              let regex = Regex::new(&format!("^(?:{})$", regex))
+                .map_err(|_| format!("Invalid regex in segment `{}`.", segment))?;
+              Segment::Param(name.to_string(), Some(regex))
+            }
+            None => Segment::Param(param.to_string(), None),
+          },
+          None => Segment::Literal(segment.to_string()),
+        },
+      };
+      segments.push(segment);
+    }
 
-  // Read the headers.
-  let mut headers = HashMap::new();
-  loop {
-    let mut line = String::new();
-    buf_reader.read_line(&mut line)?;
-    if line.trim().is_empty() {
-      break;
+    Ok(PathPattern { segments })
+  }
+
+  /// Returns whether a segment of a pattern is a literal, which matches only
+  /// itself, rather than a parameter or a wildcard.
+  pub fn is_literal(segment: &str) -> bool {
+    !segment.starts_with(':') && segment != "*" && segment != "**"
+  }
+
+  /// Returns how specific the pattern is, segment by segment, so that comparing
+  /// the results ranks `users/me` over `users/:id(\d+)`, which is ranked over
+  /// `users/:id`, then `users/*` and finally `users/**`. The pattern's end
+  /// ranks above a `**`, so that `users` is ranked over `users/**`.
+  pub fn specificity(&self) -> Vec<u8> {
+    self
+      .segments
+      .iter()
+      .map(|segment| match segment {
+        Segment::Literal(_) => 5,
+        Segment::Param(_, Some(_)) => 4,
+        Segment::Param(_, None) => 3,
+        Segment::Wildcard(_) => 2,
+        Segment::Rest(_) => 0,
+      })
+      .chain([1])
+      .collect()
+  }
+
+  /// Matches a path, returning the captured parameters.
+  pub fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+    let path_segments: Vec<&str> = path.split('/').collect();
+    let mut params = HashMap::new();
+
+    if match_segments(&self.segments, &path_segments, &mut params) {
+      Some(params)
+    } else {
+      None
+    }
+  }
+}
+
+fn match_segments(
+  pattern: &[Segment],
+  path: &[&str],
+  params: &mut HashMap<String, String>,
+) -> bool {
+  let (segment, rest) = match pattern.split_first() {
+    Some(first) => first,
+    None => return path.is_empty(),
+  };
+
+  // A `**` tries to capture as few segments as possible, backtracking when the
+  // rest of the pattern does not match.
+  if let Segment::Rest(name) = segment {
+    for taken in 0..=path.len() {
+      if match_segments(rest, &path[taken..], params) {
+        params.insert(name.clone(), decode_uri_component(&path[..taken].join("/")));
+        return true;
+      }
     }
-    if let Some(pos) = line.find(':') {
-      let key = line[..pos].trim().to_owned();
-      let value = line[pos + 1..].trim().to_owned();
-      headers.insert(key, value);
+    return false;
+  }
+
+  let (value, path_rest) = match path.split_first() {
+    Some(first) => first,
+    None => return false,
+  };
+  let capture = match segment {
+    Segment::Literal(literal) => {
+      if literal != value {
+        return false;
+      }
+      None
     }
+    Segment::Param(name, regex) => {
+      let value = decode_uri_component(value);
+      if value.is_empty() || regex.as_ref().is_some_and(|r| !r.is_match(&value)) {
+        return false;
+      }
+      Some((name, value))
+    }
+    Segment::Wildcard(name) => {
+      if value.is_empty() {
+        return false;
+      }
+      Some((name, decode_uri_component(value)))
+    }
+    Segment::Rest(_) => unreachable!(),
+  };
+
+  if !match_segments(rest, path_rest, params) {
+    return false;
+  }
+  if let Some((name, value)) = capture {
+    params.insert(name.clone(), value);
   }
+  true
+}
 
-  // Read the body.
-  let mut body = String::new();
-  if method == "POST" || method == "PUT" {
-    let content_length = headers
-      .get("Content-Length")
-      .and_then(|v| v.parse::<usize>().ok())
-      .unwrap_or(0);
+/// Reads a request from a connection. Returns `None` when the client closed the
+/// connection before sending another request.
+///
+/// When the client waits for a `100 Continue` before sending the body, the
+/// interim response is written to `interim`.
+pub fn parse_request<R: BufRead, W: Write>(
+  buf_reader: &mut R,
+  interim: &mut W,
+  limits: &Limits,
+) -> Result<Option<Request>, ParseError> {
+  // Ignore the empty lines that some clients send between pipelined requests.
+  let mut start_line = String::new();
+  while start_line.is_empty() {
+    match read_line(buf_reader, limits.max_request_line_length) {
+      Ok(Some(line)) => start_line = line,
+      Ok(None) => return Ok(None),
+      Err(ParseError::LineTooLong) => return Err(ParseError::UriTooLong),
+      Err(error) => return Err(error),
+    }
+  }
 
-    if content_length > 0 {
-      let mut buffer = vec![0; content_length];
-      buf_reader.read_exact(&mut buffer)?;
-      body = String::from_utf8(buffer).unwrap();
+  let start_line_parts: Vec<&str> = start_line.split(' ').collect();
+  let (method, path, version) = match start_line_parts[..] {
+    [method, path, version] if is_token(method) && !path.is_empty() => (method, path, version),
+    _ => return Err(ParseError::BadRequest("Malformed request line.")),
+  };
+  match version.strip_prefix("HTTP/").map(|v| v.split_once('.')) {
+    Some(Some(("1", minor))) if !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit()) => {}
+    Some(Some((major, _))) if !major.is_empty() && major.chars().all(|c| c.is_ascii_digit()) => {
+      return Err(ParseError::VersionNotSupported)
     }
+    _ => return Err(ParseError::BadRequest("Malformed HTTP version.")),
   }
 
-  Ok(Request {
-    path,
-    version,
-    method,
-    headers,
-    body,
+  let mut request = Request {
+    path: path.to_owned(),
+    query: String::new(),
+    version: version.to_owned(),
+    method: method.to_uppercase(),
+    headers: read_headers(buf_reader, limits)?,
+    body: Vec::new(),
     queries: HashMap::new(),
     params: HashMap::new(),
     matches: Vec::new(),
-  })
+  };
+
+  // Any method may have a body, whose length is either given upfront or
+  // determined by the chunked encoding.
+  let body_length = body_length(&request, limits)?;
+  if body_length == BodyLength::None {
+    return Ok(Some(request));
+  }
+
+  if let Some(expect) = request.header("Expect") {
+    if !expect.eq_ignore_ascii_case("100-continue") {
+      return Err(ParseError::ExpectationFailed);
+    }
+    if request.version != "HTTP/1.0" {
+      interim
+        .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+        .and_then(|_| interim.flush())
+        .map_err(ParseError::Io)?;
+    }
+  }
+
+  request.body = match body_length {
+    BodyLength::Fixed(length) => {
+      let mut buffer = vec![0; length];
+      buf_reader
+        .read_exact(&mut buffer)
+        .map_err(eof_as_bad_request)?;
+      buffer
+    }
+    _ => {
+      let (body, trailers) = read_chunked(buf_reader, limits)?;
+      for (key, value) in trailers {
+        request.headers.entry(key).or_insert(value);
+      }
+      body
+    }
+  };
+  Ok(Some(request))
+}
+
+#[derive(PartialEq, Debug)]
+enum BodyLength {
+  None,
+  Fixed(usize),
+  Chunked,
+}
+
+fn body_length(request: &Request, limits: &Limits) -> Result<BodyLength, ParseError> {
+  if let Some(encoding) = request.header("Transfer-Encoding") {
+    // The chunked encoding has to be the last one applied, otherwise the end of
+    // the body can not be determined.
+    let last = encoding.rsplit(',').next().unwrap_or("").trim();
+    return if last.eq_ignore_ascii_case("chunked") {
+      Ok(BodyLength::Chunked)
+    } else {
+      Err(ParseError::BadRequest("Unsupported Transfer-Encoding."))
+    };
+  }
+
+  match request.header("Content-Length") {
+    Some(value) => {
+      let length = value
+        .parse::<usize>()
+        .map_err(|_| ParseError::BadRequest("Malformed Content-Length."))?;
+      if length > limits.max_body_size {
+        Err(ParseError::PayloadTooLarge)
+      } else if length == 0 {
+        Ok(BodyLength::None)
+      } else {
+        Ok(BodyLength::Fixed(length))
+      }
+    }
+    None => Ok(BodyLength::None),
+  }
+}
+
+/// Reads header lines up to the empty line that ends them.
+pub fn read_headers<R: BufRead>(
+  buf_reader: &mut R,
+  limits: &Limits,
+) -> Result<HashMap<String, String>, ParseError> {
+  let mut headers: HashMap<String, String> = HashMap::new();
+  let mut headers_size = 0;
+  let mut header_count = 0;
+  loop {
+    let remaining = limits.max_header_size.saturating_sub(headers_size);
+    let line = match read_line(buf_reader, remaining) {
+      Ok(Some(line)) => line,
+      Ok(None) => return Err(ParseError::BadRequest("Unexpected end of request.")),
+      Err(ParseError::LineTooLong) => return Err(ParseError::HeadersTooLarge),
+      Err(error) => return Err(error),
+    };
+    if line.is_empty() {
+      return Ok(headers);
+    }
+
+    headers_size += line.len();
+    header_count += 1;
+    if header_count > limits.max_header_count {
+      return Err(ParseError::HeadersTooLarge);
+    }
+
+    // Neither obsolete line folding nor whitespace between a header's name and
+    // the colon are allowed, as they are used to smuggle requests.
+    let (key, value) = match line.split_once(':') {
+      Some((key, value)) if is_token(key) => (key, value.trim()),
+      _ => return Err(ParseError::BadRequest("Malformed header.")),
+    };
+    insert_header(&mut headers, key, value);
+  }
+}
+
+/// Adds a header, combining repeated ones into a comma-separated list. The
+/// values of `Set-Cookie` may contain commas themselves, so they are kept on
+/// separate lines instead, which `push_header` writes as separate headers.
+pub fn insert_header(headers: &mut HashMap<String, String>, key: &str, value: &str) {
+  let separator = if key.eq_ignore_ascii_case("Set-Cookie") {
+    "\n"
+  } else {
+    ", "
+  };
+  headers
+    .entry(key.to_owned())
+    .and_modify(|v| {
+      v.push_str(separator);
+      v.push_str(value);
+    })
+    .or_insert_with(|| value.to_owned());
+}
+
+/// Writes a header line into a message's head, or a line per line of its value,
+/// so that a line break never ends up on the wire.
+pub fn push_header(head: &mut String, key: &str, value: &str) {
+  if value.is_empty() {
+    head.push_str(&format!("{}: \r\n", key));
+  }
+  for line in value.split(['\r', '\n']).filter(|line| !line.is_empty()) {
+    head.push_str(&format!("{}: {}\r\n", key, line));
+  }
+}
+
+/// Reads a body sent with the chunked transfer coding, returning the decoded
+/// body and the trailer fields that follow it.
+pub fn read_chunked<R: BufRead>(
+  buf_reader: &mut R,
+  limits: &Limits,
+) -> Result<(Vec<u8>, HashMap<String, String>), ParseError> {
+  let mut body = Vec::new();
+
+  loop {
+    let line = read_line(buf_reader, limits.max_request_line_length)?
+      .ok_or(ParseError::BadRequest("Unexpected end of request."))?;
+    // Chunk extensions are allowed after the size, but have no meaning here.
+    let size = line.split(';').next().unwrap_or("").trim();
+    let size = usize::from_str_radix(size, 16)
+      .map_err(|_| ParseError::BadRequest("Malformed chunk size."))?;
+    if size == 0 {
+      break;
+    }
+    // The size may be as large as `usize` allows, so it is compared with the
+    // room left rather than added to the body's length.
+    if size > limits.max_body_size - body.len() {
+      return Err(ParseError::PayloadTooLarge);
+    }
+
+    let start = body.len();
+    body.resize(start + size, 0);
+    buf_reader
+      .read_exact(&mut body[start..])
+      .map_err(eof_as_bad_request)?;
+    if read_line(buf_reader, 0)? != Some(String::new()) {
+      return Err(ParseError::BadRequest("Malformed chunk."));
+    }
+  }
+
+  // The fields that frame the message must not be sent as trailers.
+  let mut trailers = read_headers(buf_reader, limits)?;
+  trailers.retain(|key, _| {
+    !key.eq_ignore_ascii_case("Content-Length") && !key.eq_ignore_ascii_case("Transfer-Encoding")
+  });
+
+  Ok((body, trailers))
+}
+
+/// Reads a line of at most `limit` bytes, without its line terminator. Returns
+/// `None` when the connection was closed before the line started.
+fn read_line<R: BufRead>(buf_reader: &mut R, limit: usize) -> Result<Option<String>, ParseError> {
+  let mut line = Vec::new();
+  // Allow for the line terminator, which is not part of the limit.
+  buf_reader
+    .take(limit as u64 + 2)
+    .read_until(b'\n', &mut line)
+    .map_err(ParseError::Io)?;
+
+  if line.is_empty() {
+    return Ok(None);
+  }
+  if line.last() != Some(&b'\n') {
+    return if line.len() > limit {
+      Err(ParseError::LineTooLong)
+    } else {
+      Err(ParseError::BadRequest("Unexpected end of request."))
+    };
+  }
+
+  line.pop();
+  if line.last() == Some(&b'\r') {
+    line.pop();
+  }
+  if line.len() > limit {
+    return Err(ParseError::LineTooLong);
+  }
+
+  String::from_utf8(line)
+    .map(Some)
+    .map_err(|_| ParseError::BadRequest("Invalid characters."))
+}
+
+/// Returns whether a string is a valid token, such as a method or header name.
+pub(crate) fn is_token(value: &str) -> bool {
+  !value.is_empty()
+    && value
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+/// A request that ends early is malformed, whereas other IO errors mean the
+/// connection itself failed.
+fn eof_as_bad_request(error: IoError) -> ParseError {
+  if error.kind() == ErrorKind::UnexpectedEof {
+    ParseError::BadRequest("Unexpected end of request.")
+  } else {
+    ParseError::Io(error)
+  }
+}
+
+/// Returns whether the client wants to send more requests on the connection.
+/// HTTP/1.1 connections are persistent unless the client asks to close them,
+/// whereas HTTP/1.0 clients have to ask to keep them open.
+pub fn wants_keep_alive(request: &Request) -> bool {
+  let has_token = |token: &str| {
+    request
+      .header("Connection")
+      .is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+  };
+
+  if request.version == "HTTP/1.0" {
+    has_token("keep-alive")
+  } else {
+    !has_token("close")
+  }
+}
+
+/// Closes a connection after a response to a request that was not read
+/// completely. Closing a socket with unread data makes the peer discard the
+/// response, so the rest of the request is drained for a little while first.
+pub fn linger<R: Read>(stream: &TcpStream, reader: &mut R) {
+  if stream.shutdown(Shutdown::Write).is_err() {
+    return;
+  }
+  if stream
+    .set_read_timeout(Some(Duration::from_millis(500)))
+    .is_ok()
+  {
+    let _ = io::copy(&mut reader.take(1024 * 1024), &mut io::sink());
+  }
+}
+
+/// Resets a connection, so that the client gets an error rather than the end of
+/// the stream. Closing a socket whose linger timeout is zero sends a `RST`
+/// instead of a `FIN`. When the option can not be set, the connection is only
+/// shut down.
+///
+/// The option's constants are only known for the targets listed here; other
+/// architectures, such as mips and sparc, number them differently.
+#[cfg(any(
+  all(
+    any(target_os = "linux", target_os = "android"),
+    any(
+      target_arch = "x86",
+      target_arch = "x86_64",
+      target_arch = "arm",
+      target_arch = "aarch64",
+      target_arch = "riscv64"
+    )
+  ),
+  target_os = "macos",
+  target_os = "ios",
+  target_os = "freebsd",
+  target_os = "openbsd",
+  target_os = "netbsd",
+  target_os = "dragonfly"
+))]
+pub fn reset(stream: &TcpStream) {
+  use std::os::{
+    fd::AsRawFd,
+    raw::{c_int, c_void},
+  };
+
+  #[repr(C)]
+  struct Linger {
+    l_onoff: c_int,
+    l_linger: c_int,
+  }
+
+  extern "C" {
+    fn setsockopt(
+      socket: c_int,
+      level: c_int,
+      name: c_int,
+      value: *const c_void,
+      len: u32,
+    ) -> c_int;
+  }
+
+  // `SOL_SOCKET` and `SO_LINGER`.
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  const SOCKET_OPTIONS: (c_int, c_int) = (1, 13);
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  const SOCKET_OPTIONS: (c_int, c_int) = (0xffff, 0x0080);
+
+  let (level, name) = SOCKET_OPTIONS;
+  let linger = Linger {
+    l_onoff: 1,
+    l_linger: 0,
+  };
+  // SAFETY: The descriptor is open for as long as `stream` lives, and the
+  // option's value is a `struct linger` of the size given.
+  let result = unsafe {
+    setsockopt(
+      stream.as_raw_fd(),
+      level,
+      name,
+      &linger as *const Linger as *const c_void,
+      std::mem::size_of::<Linger>() as u32,
+    )
+  };
+  if result != 0 {
+    let _ = stream.shutdown(Shutdown::Both);
+  }
+}
+
+/// Without known socket options, the connection is only shut down.
+#[cfg(not(any(
+  all(
+    any(target_os = "linux", target_os = "android"),
+    any(
+      target_arch = "x86",
+      target_arch = "x86_64",
+      target_arch = "arm",
+      target_arch = "aarch64",
+      target_arch = "riscv64"
+    )
+  ),
+  target_os = "macos",
+  target_os = "ios",
+  target_os = "freebsd",
+  target_os = "openbsd",
+  target_os = "netbsd",
+  target_os = "dragonfly"
+)))]
+pub fn reset(stream: &TcpStream) {
+  let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Returns the reason phrase of a status code.
+pub fn reason_phrase(status: u16) -> &'static str {
+  match status {
+    100 => "Continue",
+    200 => "OK",
+    201 => "Created",
+    202 => "Accepted",
+    204 => "No Content",
+    301 => "Moved Permanently",
+    302 => "Found",
+    304 => "Not Modified",
+    400 => "Bad Request",
+    401 => "Unauthorized",
+    403 => "Forbidden",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    408 => "Request Timeout",
+    409 => "Conflict",
+    413 => "Content Too Large",
+    417 => "Expectation Failed",
+    414 => "URI Too Long",
+    422 => "Unprocessable Content",
+    429 => "Too Many Requests",
+    431 => "Request Header Fields Too Large",
+    500 => "Internal Server Error",
+    501 => "Not Implemented",
+    502 => "Bad Gateway",
+    503 => "Service Unavailable",
+    504 => "Gateway Timeout",
+    505 => "HTTP Version Not Supported",
+    _ => "",
+  }
+}
+
+const FRAMING_HEADERS: [&str; 4] = [
+  "Content-Length",
+  "Transfer-Encoding",
+  "Connection",
+  "Keep-Alive",
+];
+
+/// What is sent instead of a response for the `Malformed` fault.
+const MALFORMED_RESPONSE: &[u8] = b"HTTP/1.1 ??? Malformed\r\nContent-Length: nope\r\n\x00\r\n";
+
+/// Writes a response to a connection, along with its fault if it has one. The
+/// `Close` and `Reset` faults write nothing.
+pub fn write_response<W: Write>(
+  writer: &mut W,
+  response: Response,
+  is_head: bool,
+  connection: Connection,
+) -> Result<(), IoError> {
+  let mut head = format!(
+    "HTTP/1.1 {} {}\r\n",
+    response.status,
+    reason_phrase(response.status)
+  );
+  // The server frames the message itself, so the framing headers set by the
+  // handler are ignored.
+  for (key, value) in response.headers.iter() {
+    if FRAMING_HEADERS.iter().any(|h| key.eq_ignore_ascii_case(h)) {
+      continue;
+    }
+    push_header(&mut head, key, value);
+  }
+  match connection {
+    Connection::KeepAlive(timeout) => {
+      head.push_str("Connection: keep-alive\r\n");
+      // A sub-second timeout is advertised as a second rather than none.
+      let seconds = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+      head.push_str(&format!("Keep-Alive: timeout={}\r\n", seconds));
+    }
+    Connection::Close => head.push_str("Connection: close\r\n"),
+  }
+  // A response to a `HEAD` request carries the headers of the equivalent `GET`
+  // response, including its `Content-Length`, but never a body.
+  head.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+
+  let body: &[u8] = if is_head { &[] } else { &response.body };
+  match response.fault {
+    None => {
+      let mut bytes = head.into_bytes();
+      bytes.extend_from_slice(body);
+
+      // The write_all method on stream takes a &[u8] and sends those bytes
+      // directly down the connection.
+      writer.write_all(&bytes)?;
+    }
+    Some(Fault::Close | Fault::Reset) => return Ok(()),
+    Some(Fault::Truncate) => {
+      writer.write_all(head.as_bytes())?;
+      writer.write_all(&body[..body.len() / 2])?;
+    }
+    Some(Fault::Malformed) => writer.write_all(MALFORMED_RESPONSE)?,
+    Some(Fault::Drip {
+      chunk_size,
+      interval,
+    }) => {
+      writer.write_all(head.as_bytes())?;
+      for chunk in body.chunks(chunk_size.max(1)) {
+        writer.flush()?;
+        thread::sleep(interval);
+        writer.write_all(chunk)?;
+      }
+    }
+  }
+  writer.flush()
 }
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use regex::Regex;
+use std::{collections::HashMap, fmt, io::Error as IoError, time::Duration};
 
 #[derive(PartialEq, Debug)]
 pub struct RequestPath {
   pub path: String,
+  pub query: String,
   pub queries: HashMap<String, String>,
   pub params: HashMap<String, String>,
   pub matches: Vec<String>,
@@ -12,26 +14,72 @@ pub struct RequestPath {
 pub struct Request {
   pub method: String,
   pub path: String,
+  /// The raw query string, without the `?`.
+  pub query: String,
   pub version: String,
   pub headers: HashMap<String, String>,
-  pub body: String,
+  pub body: Vec<u8>,
   pub queries: HashMap<String, String>,
   pub params: HashMap<String, String>,
   pub matches: Vec<String>,
 }
 
+/// The method a listener accepts.
+#[derive(PartialEq, Debug, Clone)]
 pub enum Method {
   Get,
   Post,
   Put,
+  Patch,
+  Delete,
+  Head,
+  Options,
+  /// A non-standard verb, e.g. `PURGE` or `PROPFIND`.
+  Custom(String),
+  /// Matches every method.
+  Any,
 }
 
 impl Method {
-  pub fn to_string(&self) -> String {
+  /// Returns whether a request with the given method should be dispatched to a
+  /// listener of this method. A `GET` listener also answers `HEAD` requests.
+  pub fn matches(&self, method: &str) -> bool {
     match self {
-      Method::Get => String::from("GET"),
-      Method::Post => String::from("POST"),
-      Method::Put => String::from("PUT"),
+      Method::Any => true,
+      Method::Get => matches!(Method::from(method), Method::Get | Method::Head),
+      _ => Method::from(method) == *self,
+    }
+  }
+}
+
+impl From<&str> for Method {
+  fn from(method: &str) -> Self {
+    match method.to_uppercase().as_str() {
+      "GET" => Method::Get,
+      "POST" => Method::Post,
+      "PUT" => Method::Put,
+      "PATCH" => Method::Patch,
+      "DELETE" => Method::Delete,
+      "HEAD" => Method::Head,
+      "OPTIONS" => Method::Options,
+      "*" => Method::Any,
+      method => Method::Custom(method.to_string()),
+    }
+  }
+}
+
+impl fmt::Display for Method {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Method::Get => write!(f, "GET"),
+      Method::Post => write!(f, "POST"),
+      Method::Put => write!(f, "PUT"),
+      Method::Patch => write!(f, "PATCH"),
+      Method::Delete => write!(f, "DELETE"),
+      Method::Head => write!(f, "HEAD"),
+      Method::Options => write!(f, "OPTIONS"),
+      Method::Custom(method) => write!(f, "{}", method.to_uppercase()),
+      Method::Any => write!(f, "*"),
     }
   }
 }
@@ -41,6 +89,22 @@ pub enum RequestPathPattern {
   Match(String),
 }
 
+/// A parsed path pattern, e.g. `users/:id/**`.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+  pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Segment {
+  Literal(String),
+  Param(String, Option<Regex>),
+  /// `*`, captured under its index among the wildcards.
+  Wildcard(String),
+  /// `**`, captured under its index among the wildcards.
+  Rest(String),
+}
+
 pub struct RequestOption {
   pub method: Method,
   pub path: RequestPathPattern,
@@ -49,8 +113,108 @@ pub struct RequestOption {
 /// A data structure that represents a response.
 pub struct Response {
   pub status: u16,
-  pub body: String,
+  pub body: Vec<u8>,
   pub headers: HashMap<String, String>,
+  /// A failure to inject while writing the response, if any.
+  pub fault: Option<Fault>,
+}
+
+/// A way to fail on purpose while answering a request, to test how clients
+/// cope with broken servers.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Fault {
+  /// Closes the connection without responding.
+  Close,
+  /// Resets the connection without responding.
+  Reset,
+  /// Sends the head and half of the body, then closes the connection.
+  Truncate,
+  /// Sends bytes that are not an HTTP response, then closes the connection.
+  Malformed,
+  /// Sends the body in chunks of `chunk_size` bytes, waiting `interval` before
+  /// each of them.
+  Drip {
+    chunk_size: usize,
+    interval: Duration,
+  },
+}
+
+impl Fault {
+  /// Returns whether the connection can serve more requests after the fault.
+  pub fn keeps_connection(&self) -> bool {
+    matches!(self, Fault::Drip { .. })
+  }
+}
+
+/// The limits a request has to stay within.
+pub struct Limits {
+  pub max_request_line_length: usize,
+  pub max_header_count: usize,
+  /// The maximum size of all header lines together, in bytes.
+  pub max_header_size: usize,
+  pub max_body_size: usize,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits {
+      max_request_line_length: 8 * 1024,
+      max_header_count: 100,
+      max_header_size: 64 * 1024,
+      max_body_size: 16 * 1024 * 1024,
+    }
+  }
+}
+
+/// Why a request could not be parsed.
+#[derive(Debug)]
+pub enum ParseError {
+  /// The connection failed or timed out, so no response can be sent.
+  Io(IoError),
+  BadRequest(&'static str),
+  /// A line exceeded the limit it was read with.
+  LineTooLong,
+  UriTooLong,
+  HeadersTooLarge,
+  PayloadTooLarge,
+  ExpectationFailed,
+  VersionNotSupported,
+}
+
+impl ParseError {
+  /// Returns the status of the response to send to the client, if any.
+  pub fn status(&self) -> Option<u16> {
+    match self {
+      ParseError::Io(_) => None,
+      ParseError::BadRequest(_) | ParseError::LineTooLong => Some(400),
+      ParseError::UriTooLong => Some(414),
+      ParseError::HeadersTooLarge => Some(431),
+      ParseError::PayloadTooLarge => Some(413),
+      ParseError::ExpectationFailed => Some(417),
+      ParseError::VersionNotSupported => Some(505),
+    }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ParseError::Io(error) => write!(f, "{}", error),
+      ParseError::BadRequest(message) => write!(f, "{}", message),
+      ParseError::LineTooLong => write!(f, "Line too long."),
+      ParseError::UriTooLong => write!(f, "Request line too long."),
+      ParseError::HeadersTooLarge => write!(f, "Request headers too large."),
+      ParseError::PayloadTooLarge => write!(f, "Request body too large."),
+      ParseError::ExpectationFailed => write!(f, "Expectation not supported."),
+      ParseError::VersionNotSupported => write!(f, "HTTP version not supported."),
+    }
+  }
+}
+
+/// What happens to a connection once a response is written.
+pub enum Connection {
+  KeepAlive(Duration),
+  Close,
 }
 
 /// A data structure that similar to a [HashMap].
@@ -71,7 +235,7 @@ impl Nested {
     self.insert(key, NestedValue::Str(value));
   }
 
-  pub fn iter(&self) -> std::slice::Iter<(String, NestedValue)> {
+  pub fn iter(&self) -> std::slice::Iter<'_, (String, NestedValue)> {
     self.values.iter()
   }
 
@@ -88,3 +252,27 @@ pub enum NestedValue {
   // Int(i32),
   // Float(f32),
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn methods_are_parsed_case_insensitively() {
+    assert_eq!(Method::from("get"), Method::Get);
+    assert_eq!(Method::from("OPTIONS"), Method::Options);
+    assert_eq!(Method::from("*"), Method::Any);
+    assert_eq!(Method::from("purge"), Method::Custom(String::from("PURGE")));
+    assert_eq!(Method::from("purge").to_string(), "PURGE");
+  }
+
+  #[test]
+  fn listeners_accept_their_method() {
+    assert!(Method::Post.matches("POST"));
+    assert!(!Method::Post.matches("PUT"));
+    assert!(Method::Get.matches("HEAD"));
+    assert!(!Method::Head.matches("GET"));
+    assert!(Method::Custom(String::from("PURGE")).matches("PURGE"));
+    assert!(Method::Any.matches("PROPFIND"));
+  }
+}